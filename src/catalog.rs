@@ -0,0 +1,269 @@
+//! Catalog of known item names, e.g. from the SOEP metadatabase. The catalog expands item
+//! sequences of quantifiers into plain filters.
+//!
+//! Ex.: "any(hl0010..hl0012) = 1" -> "hl0010=1 | hl0011=1 | hl0012=1"
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::expr::{fold_expr, Comparison, Expr, Fold, Ident, Logic, Operand, Quantifier, Selector};
+use crate::span::Span;
+
+/// Set of item names, ordered by name.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Catalog {
+    items: BTreeSet<String>,
+}
+
+/// Error of an expansion. Patterns must match at least one item of the catalog.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpansionError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl fmt::Display for ExpansionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{} ({})", self.message, self.span)
+    }
+}
+
+impl Catalog {
+    pub fn new() -> Self {
+        Catalog { items: BTreeSet::new() }
+    }
+
+    /// Reads one item name per line. Empty lines are skipped.
+    pub fn from_lines(text: &str) -> Self {
+        text.lines().map(str::trim).filter(|line| !line.is_empty()).collect()
+    }
+
+    pub fn insert(&mut self, item: &str) {
+        self.items.insert(item.to_string());
+    }
+
+    pub fn contains(&self, item: &str) -> bool {
+        self.items.contains(item)
+    }
+
+    /// Returns all item names in order.
+    pub fn items(&self) -> impl Iterator<Item = &str> {
+        self.items.iter().map(String::as_str)
+    }
+
+    /// Returns the items starting with the pattern before '*', ex.: "hl00*".
+    pub fn matching(&self, pattern: &str) -> Vec<&str> {
+        let prefix = pattern.trim_end_matches('*');
+        self.items().filter(|item| item.starts_with(prefix)).collect()
+    }
+
+    /// Returns the known item closest to `item` by edit distance, ex.: "hl0001" for "hl001".
+    /// Items that differ in more than a third of the characters of `item` are not suggested.
+    pub fn suggest(&self, item: &str) -> Option<&str> {
+        let limit = (item.chars().count() / 3).max(1);
+        self.items()
+            .map(|known| (edit_distance(item, known), known))
+            .filter(|(distance, _)| *distance <= limit)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, known)| known)
+    }
+
+    /// Replaces all quantifiers in `expr` by chains of filters, joined by '|' for `any` and by
+    /// '&' for `all`. Item ranges are expanded by number, patterns by the items in the catalog.
+    /// The new filters take the span of the quantifier.
+    pub fn expand(&self, expr: Expr) -> Result<Expr, ExpansionError> {
+        let mut expand = Expand { catalog: self, error: None };
+        let expr = expand.fold_expr(expr);
+        match expand.error {
+            Some(error) => Err(error),
+            None => Ok(expr),
+        }
+    }
+}
+
+impl<'a> FromIterator<&'a str> for Catalog {
+    fn from_iter<T: IntoIterator<Item = &'a str>>(iter: T) -> Self {
+        Catalog { items: iter.into_iter().map(str::to_string).collect() }
+    }
+}
+
+/// Largest number of items of an item range, ex.: "hl0001..hl9999".
+pub const MAX_RANGE_ITEMS: u64 = 10_000;
+
+/// Checks that `start..end` is a valid item range, ex.: "hl0010..hl0025". Both items need the
+/// same prefix and the same number of digits, `start` must not come after `end`, and the range
+/// must not have more than `MAX_RANGE_ITEMS` items.
+pub fn check_item_range(start: &str, end: &str) -> Result<(), String> {
+    range_bounds(start, end).map(|_| ())
+}
+
+/// Returns the items of the item range `start..end`, ex.: "hl0010", "hl0011", ..., "hl0025".
+pub fn item_range(start: &str, end: &str) -> Result<Vec<String>, String> {
+    let (prefix, first, last, width) = range_bounds(start, end)?;
+    Ok((first..=last).map(|number| format!("{}{:0width$}", prefix, number)).collect())
+}
+
+/// Returns the prefix, the first and last number, and the number of digits of the item range
+/// `start..end`, without building its items.
+fn range_bounds<'a>(start: &'a str, end: &str) -> Result<(&'a str, u64, u64, usize), String> {
+    let (prefix, digits) = split_number(start)
+        .ok_or(format!("Item range requires items ending in a number, not '{}'", start))?;
+    let (end_prefix, end_digits) = split_number(end)
+        .ok_or(format!("Item range requires items ending in a number, not '{}'", end))?;
+    if prefix != end_prefix {
+        return Err(format!("Expected item with prefix '{}' after '..'", prefix))
+    }
+    if digits.len() != end_digits.len() {
+        return Err(format!("Expected item number with {} digits after '..'", digits.len()))
+    }
+    let (first, last) = match (digits.parse::<u64>(), end_digits.parse::<u64>()) {
+        (Ok(first), Ok(last)) => (first, last),
+        _ => return Err("Item number is too large".to_string()),
+    };
+    if first > last {
+        return Err(format!("Expected item after '{}'", start))
+    }
+    if last - first >= MAX_RANGE_ITEMS {
+        return Err(format!("Item range has more than {} items", MAX_RANGE_ITEMS))
+    }
+    Ok((prefix, first, last, digits.len()))
+}
+
+/// Splits an item name into its prefix and its trailing digits, ex.: "hl0010" -> ("hl", "0010").
+fn split_number(item: &str) -> Option<(&str, &str)> {
+    let prefix = item.trim_end_matches(|c: char| c.is_ascii_digit());
+    let digits = &item[prefix.len()..];
+    if digits.is_empty() { None } else { Some((prefix, digits)) }
+}
+
+/// Returns the number of characters to insert, delete or replace to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let replace = previous[j] + usize::from(a_char != *b_char);
+            current.push(replace.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Replaces quantifiers by filter chains.
+struct Expand<'a> {
+    catalog: &'a Catalog,
+    error: Option<ExpansionError>,
+}
+
+impl Expand<'_> {
+    /// Returns the items of a selector with its question.
+    fn items(&mut self, selector: &Selector) -> Vec<Operand> {
+        let span = selector.span();
+        let names = match selector {
+            Selector::Item { item, .. } => vec![item.name.clone()],
+            Selector::Range { start, end, .. } => match item_range(&start.name, &end.name) {
+                Ok(names) => names,
+                Err(message) => {
+                    self.error.get_or_insert(ExpansionError { message, span });
+                    Vec::new()
+                },
+            },
+            Selector::Pattern { pattern, .. } => {
+                let names: Vec<String> = self.catalog.matching(&pattern.name).into_iter().map(str::to_string).collect();
+                if names.is_empty() {
+                    let message = format!("No items match '{}'", pattern.name);
+                    self.error.get_or_insert(ExpansionError { message, span });
+                }
+                names
+            },
+        };
+        names.into_iter()
+            .map(|name| Operand::Item { question: selector.question().cloned(), item: Ident { name, span }, span })
+            .collect()
+    }
+}
+
+impl Fold for Expand<'_> {
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        let Expr::Quantified { quantifier, items, operator, right, span } = expr else {
+            return fold_expr(self, expr)
+        };
+        let logic = match quantifier {
+            Quantifier::Any => Logic::Or,
+            Quantifier::All => Logic::And,
+        };
+        let filter = |left: Operand, operator: Comparison| Expr::Filter { left, operator, right: right.clone(), span };
+
+        let mut filters = items.iter().flat_map(|selector| self.items(selector)).map(|item| filter(item, operator));
+        let Some(first) = filters.next() else {
+            return Expr::Quantified { quantifier, items, operator, right, span }
+        };
+        filters.fold(first, |left, right| Expr::Logical { left: Box::new(left), operator: logic, right: Box::new(right), span })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_item_range() {
+        assert_eq!(item_range("hl0009", "hl0011").unwrap(), vec!["hl0009", "hl0010", "hl0011"]);
+        assert_eq!(item_range("e1", "e1").unwrap(), vec!["e1"]);
+
+        let errors = vec![
+            ("hl", "hl0011", "Item range requires items ending in a number, not 'hl'"),
+            ("hl0010", "pl0011", "Expected item with prefix 'hl' after '..'"),
+            ("hl0010", "hl011", "Expected item number with 4 digits after '..'"),
+            ("hl0010", "hl0009", "Expected item after 'hl0010'"),
+            ("a000000000", "a999999999", "Item range has more than 10000 items"),
+            ("hl0001", "hl10001", "Expected item number with 4 digits after '..'"),
+        ];
+        for (start, end, expected) in errors {
+            assert_eq!(check_item_range(start, end), Err(expected.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_suggest() {
+        assert_eq!(edit_distance("hl001", "hl0001"), 1);
+        assert_eq!(edit_distance("hl0001", "lh0001"), 2);
+        assert_eq!(edit_distance("", "hl"), 2);
+
+        let catalog = Catalog::from_lines("hl0001\nhl0010\npl0001\nelb0001");
+        let cases = vec![
+            ("hl001", Some("hl0001")),
+            ("hl0011", Some("hl0001")),
+            ("pl0010", Some("hl0010")),
+            ("elb001", Some("elb0001")),
+            ("xy9999", None),
+            ("e", None),
+        ];
+        for (item, expected) in cases {
+            assert_eq!(catalog.suggest(item), expected, "Item: {}", item);
+        }
+    }
+
+    #[test]
+    fn test_expand() {
+        let catalog = Catalog::from_lines("hl0100\nhl0010\n\nhl0011\npl0001\n");
+        assert_eq!(catalog.matching("hl00*"), vec!["hl0010", "hl0011"]);
+
+        let cases = vec![
+            ("any(hl0010..hl0012) = 1", "hl0010=1 | hl0011=1 | hl0012=1"),
+            ("all(q01;hl00*, pl0001) != -1,-2", "q01;hl0010!=-1,-2 & q01;hl0011!=-1,-2 & pl0001!=-1,-2"),
+            ("pl0001 = 1 & any(hl01*, hl0010) > 2", "pl0001=1 & (hl0100>2 | hl0010>2)"),
+            ("!all(hl0010) = 1", "!hl0010=1"),
+        ];
+        for (source, expected) in cases {
+            let expr = crate::parse(source).expr.unwrap();
+            assert_eq!(catalog.expand(expr).unwrap().to_string(), expected, "Input: {}", source);
+        }
+
+        let expr = crate::parse("any(hl02*) = 1").expr.unwrap();
+        let error = catalog.expand(expr).unwrap_err();
+        assert_eq!(error.to_string(), "No items match 'hl02*' (4..9)");
+    }
+}
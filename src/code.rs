@@ -0,0 +1,431 @@
+//! Stable codes of diagnostics, e.g. "F0008" for a missing '&' or '|' between filters. Messages
+//! may change between versions, codes do not. `Code::explanation` describes a code with examples.
+
+use std::fmt;
+
+use strum::IntoEnumIterator;
+
+/// Code of a scanner or parser diagnostic. Codes are never reused for other diagnostics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, strum_macros::Display, strum_macros::EnumString, strum_macros::EnumIter)]
+pub enum Code {
+    #[strum(serialize = "F0000")]
+    Internal,
+    #[strum(serialize = "F0001")]
+    UnexpectedCharacter,
+    #[strum(serialize = "F0002")]
+    MisplacedDot,
+    #[strum(serialize = "F0003")]
+    UnterminatedString,
+    #[strum(serialize = "F0004")]
+    UnknownEscape,
+    #[strum(serialize = "F0005")]
+    UnterminatedComment,
+    #[strum(serialize = "F0006")]
+    InvalidNumber,
+    #[strum(serialize = "F0007")]
+    AlternativeOperator,
+    #[strum(serialize = "F0008")]
+    MissingLogicalOperator,
+    #[strum(serialize = "F0009")]
+    ExpectedFilter,
+    #[strum(serialize = "F0010")]
+    UnclosedBracket,
+    #[strum(serialize = "F0011")]
+    MissingItem,
+    #[strum(serialize = "F0012")]
+    ExpectedComparison,
+    #[strum(serialize = "F0013")]
+    ExpectedValue,
+    #[strum(serialize = "F0014")]
+    InvalidListElement,
+    #[strum(serialize = "F0015")]
+    OpenRange,
+    #[strum(serialize = "F0016")]
+    StringComparison,
+    #[strum(serialize = "F0017")]
+    AmbiguousMinus,
+    #[strum(serialize = "F0018")]
+    ExpectedOperand,
+    #[strum(serialize = "F0019")]
+    ExpectedIdentifier,
+    #[strum(serialize = "F0020")]
+    UnknownFunction,
+    #[strum(serialize = "F0021")]
+    ArgumentCount,
+    #[strum(serialize = "F0022")]
+    ExpectedComma,
+    #[strum(serialize = "F0023")]
+    ArgumentType,
+    #[strum(serialize = "F0024")]
+    ExpectedSelector,
+    #[strum(serialize = "F0025")]
+    InvalidItemRange,
+    #[strum(serialize = "F0026")]
+    MisplacedPattern,
+    #[strum(serialize = "F0027")]
+    ExpectedFilterName,
+    #[strum(serialize = "F0028")]
+    DuplicateFilterName,
+    #[strum(serialize = "F0029")]
+    DoubleEqual,
+    #[strum(serialize = "F0030")]
+    RedundantGrouping,
+    #[strum(serialize = "F0031")]
+    MixedLogic,
+    #[strum(serialize = "F0032")]
+    SingleElementList,
+    #[strum(serialize = "F0033")]
+    UnknownItem,
+    #[strum(serialize = "F0034")]
+    CommentNotFormatted,
+    #[strum(serialize = "F0035")]
+    MixedListTypes,
+}
+
+/// Long description of a code with examples of invalid and valid filters. For warnings,
+/// `invalid` are filters with the warning and `valid` the preferred filters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Explanation {
+    pub title: &'static str,
+    pub description: &'static str,
+    pub invalid: &'static [&'static str],
+    pub valid: &'static [&'static str],
+    pub warning: bool,
+}
+
+impl Code {
+    /// Returns all codes in order.
+    pub fn all() -> impl Iterator<Item = Code> {
+        Code::iter()
+    }
+
+    /// Returns true for codes of warnings, which do not stop parsing.
+    pub fn is_warning(self) -> bool {
+        matches!(self, Code::DoubleEqual | Code::RedundantGrouping | Code::MixedLogic | Code::SingleElementList | Code::UnknownItem)
+    }
+
+    pub fn explanation(self) -> Explanation {
+        let (title, description, invalid, valid): (_, _, &[_], &[_]) = match self {
+            Code::Internal => (
+                "Internal error",
+                "The scanner or parser failed in an unexpected way. Please report the filter that \
+                caused the error.",
+                &[], &[],
+            ),
+            Code::UnexpectedCharacter => (
+                "Unexpected character",
+                "The character is not part of the filter syntax. Identifiers consist of letters, \
+                digits and '_'.",
+                &["q01;hl0001=1 $ q02;hl0002=1", "q01;hl-0001=1@"],
+                &["q01;hl0001=1 & q02;hl0002=1"],
+            ),
+            Code::MisplacedDot => (
+                "'.' outside of a number",
+                "A '.' is only allowed as decimal separator, ex.: 1.5, and as '..' in item ranges. \
+                Question and item are separated by ';'.",
+                &["q01.hl0001=1", "hl0001=.5"],
+                &["q01;hl0001=1", "hl0001=0.5", "any(hl0001..hl0005)=1"],
+            ),
+            Code::UnterminatedString => (
+                "Unterminated string",
+                "A string has no closing quote before the end of the line. Strings start and end \
+                with the same quote, either ' or \".",
+                &["pl0001='DE", "pl0001=\"DE'"],
+                &["pl0001='DE'", "pl0001=\"DE\""],
+            ),
+            Code::UnknownEscape => (
+                "Unknown escape sequence",
+                "Strings support the escapes \\\\, \\\", \\', \\n and \\t. Any other character \
+                after '\\' is an error.",
+                &["pl0001='a\\b'"],
+                &["pl0001='a\\\\b'", "pl0001='it\\'s'"],
+            ),
+            Code::UnterminatedComment => (
+                "Unterminated block comment",
+                "A block comment starting with '/*' is not closed by '*/'. Block comments do not \
+                nest.",
+                &["hl0001=1 /* adults"],
+                &["hl0001=1 /* adults */", "hl0001=1 # adults"],
+            ),
+            Code::InvalidNumber => (
+                "Invalid number",
+                "A number can not be represented, because it is too large for a 64-bit integer.",
+                &["hl0001=99999999999999999999"],
+                &["hl0001=9999999999"],
+            ),
+            Code::AlternativeOperator => (
+                "Alternative spelling of an operator",
+                "In strict mode, only the canonical operators are allowed: '&' instead of 'and', \
+                '|' instead of 'or', '!' instead of 'not', '!=' instead of '~=' and '<>', '<=' \
+                instead of '=<', and '>=' instead of '=>'. Otherwise, alternative spellings are \
+                warnings.",
+                &["hl0001=1 and hl0002=1", "hl0001<>1"],
+                &["hl0001=1 & hl0002=1", "hl0001!=1"],
+            ),
+            Code::MissingLogicalOperator => (
+                "Missing '&' or '|' between filters",
+                "Filters must be combined with '&' or '|'.",
+                &["q01;hl0001=1 q02;hl0002=1"],
+                &["q01;hl0001=1 & q02;hl0002=1", "q01;hl0001=1 | q02;hl0002=1"],
+            ),
+            Code::ExpectedFilter => (
+                "Expected filter",
+                "A filter is missing, ex.: after '&', '|' or '!', or the filter is empty.",
+                &["", "hl0001=1 &", "hl0001=1 | !"],
+                &["hl0001=1 & hl0002=1", "!hl0002=1"],
+            ),
+            Code::UnclosedBracket => (
+                "Unclosed bracket",
+                "A bracket is not closed by the matching bracket. Filters can be grouped with \
+                (), [] and {}, arithmetic operands only with ().",
+                &["(hl0001=1 | hl0002=1", "[hl0001=1 | hl0002=1)", "(hl0001 + 1 = 2"],
+                &["(hl0001=1 | hl0002=1)", "[hl0001=1 | hl0002=1]", "(hl0001 + 1) = 2"],
+            ),
+            Code::MissingItem => (
+                "Expected item on the left-hand side",
+                "The left-hand side of a comparison must contain an item. Values are written on \
+                the right-hand side.",
+                &["1=hl0001", "'DE'=pl0001", "1 + 2 = hl0001"],
+                &["hl0001=1", "pl0001='DE'", "hl0001 + 1 = 2"],
+            ),
+            Code::ExpectedComparison => (
+                "Expected comparison",
+                "An item must be followed by one of the comparisons '=', '==', '!=', '>', '>=', \
+                '<' or '<='.",
+                &["q01;hl0001", "q01;hl0001 1"],
+                &["q01;hl0001=1", "q01;hl0001>=1"],
+            ),
+            Code::ExpectedValue => (
+                "Expected value",
+                "A comparison must be followed by a number, a string, a list, a range, an item \
+                or an arithmetic expression.",
+                &["q01;hl0001=", "hl0001=&"],
+                &["q01;hl0001=1", "hl0001=1:3", "hl0001=hl0002 + 1"],
+            ),
+            Code::InvalidListElement => (
+                "Invalid element of a list or range",
+                "Lists consist of numbers, strings and ranges, separated by ','. Ranges need a \
+                number on at least one side of ':'.",
+                &["hl0001=1,", "hl0001=:", "hl0001=1,:"],
+                &["hl0001=1,2", "hl0001=1:", "hl0001=1,:0"],
+            ),
+            Code::OpenRange => (
+                "Open range in an order comparison",
+                "With '>' and '>=', the largest value of a list or range is compared, so ranges \
+                need an upper bound. With '<' and '<=', ranges need a lower bound.",
+                &["hl0001>3:", "hl0001<=:5"],
+                &["hl0001>3:5", "hl0001<=5:", "hl0001>=:5"],
+            ),
+            Code::StringComparison => (
+                "Strings compared by order",
+                "Strings have no order and can only be compared with '=', '==' and '!='.",
+                &["pl0001>'DE'"],
+                &["pl0001='DE'", "pl0001!='DE','AT'"],
+            ),
+            Code::AmbiguousMinus => (
+                "'-' between identifiers without spaces",
+                "A '-' directly between two identifiers is most likely a mistyped ';'. \
+                Subtractions of items need spaces around '-'.",
+                &["q01-hl0001=1", "hl0001=hl0002-hl0003"],
+                &["q01;hl0001=1", "hl0001=hl0002 - hl0003"],
+            ),
+            Code::ExpectedOperand => (
+                "Expected operand",
+                "Arithmetic operators must be followed by a number, an item, a function call or \
+                an expression in parentheses.",
+                &["hl0001 + = 2", "hl0001 = 2 * ("],
+                &["hl0001 + 1 = 2", "hl0001 = 2 * (hl0002 + 1)"],
+            ),
+            Code::ExpectedIdentifier => (
+                "Expected identifier",
+                "A ';' must be preceded by a question identifier and followed by an item \
+                identifier.",
+                &["q01;=1", "q01;1=1"],
+                &["q01;hl0001=1"],
+            ),
+            Code::UnknownFunction => (
+                "Unknown function",
+                "The function is neither built in nor registered. The built-in functions are \
+                missing, count, abs, min and max.",
+                &["sum(hl0001, hl0002) = 1"],
+                &["max(hl0001, hl0002) = 1"],
+            ),
+            Code::ArgumentCount => (
+                "Wrong number of arguments",
+                "A function is called with fewer or more arguments than its signature allows.",
+                &["missing(hl0001, hl0002)", "max(hl0001) = 1"],
+                &["missing(hl0001)", "max(hl0001, hl0002) = 1"],
+            ),
+            Code::ExpectedComma => (
+                "Expected ',' or ')'",
+                "Arguments of functions and items of quantifiers are separated by ',' and closed \
+                by ')'.",
+                &["max(hl0001 hl0002) = 1", "any(hl0001 hl0002) = 1"],
+                &["max(hl0001, hl0002) = 1", "any(hl0001, hl0002) = 1"],
+            ),
+            Code::ArgumentType => (
+                "Argument or result of the wrong type",
+                "Functions returning a filter can not be used as numbers, functions expecting an \
+                item must be called with an item.",
+                &["missing(1)", "missing(hl0001) = 1", "hl0001 = missing(hl0002)"],
+                &["missing(hl0001)", "count(hl0001=1, hl0002=1) = 1"],
+            ),
+            Code::ExpectedSelector => (
+                "Expected item selector",
+                "Quantifiers take items, item ranges and item patterns, ex.: \
+                any(hl0001, hl0010..hl0015, hl01*).",
+                &["any() = 1", "any(hl0010..) = 1"],
+                &["any(hl0001) = 1", "any(hl0010..hl0015) = 1"],
+            ),
+            Code::InvalidItemRange => (
+                "Invalid item range",
+                "Both items of a range must end in a number and share the same prefix and number \
+                of digits. The first item must not come after the last item.",
+                &["any(hl0010..pl0015) = 1", "any(hl0010..hl015) = 1", "any(hl0015..hl0010) = 1"],
+                &["any(hl0010..hl0015) = 1"],
+            ),
+            Code::MisplacedPattern => (
+                "Item pattern outside of a quantifier",
+                "Patterns such as 'hl00*' select many items and are only allowed in any() and \
+                all().",
+                &["hl0001 = hl00*", "max(hl00*, hl01*) > 1"],
+                &["any(hl00*) = 1", "all(hl0001, hl01*) = hl0002"],
+            ),
+            Code::ExpectedFilterName => (
+                "Expected filter name",
+                "In documents, every filter starts on a new line with its name and ':'.",
+                &["hl0001=1"],
+                &["adults: hl0001=1"],
+            ),
+            Code::DuplicateFilterName => (
+                "Duplicate filter name",
+                "Every filter of a document must have a different name.",
+                &["adults: hl0001=1\nadults: hl0002=1"],
+                &["adults: hl0001=1\nworkers: hl0002=1"],
+            ),
+            Code::DoubleEqual => (
+                "'==' mixed with '='",
+                "'==' is the same as '='. A filter using both is harder to read, so use '=' \
+                throughout.",
+                &["hl0001==1 & hl0002=1"],
+                &["hl0001=1 & hl0002=1", "hl0001==1 & hl0002==1"],
+            ),
+            Code::RedundantGrouping => (
+                "Redundant brackets",
+                "Brackets directly around brackets have no effect.",
+                &["((hl0001=1 | hl0002=1)) & hl0003=1", "hl0001 = ((hl0002 + 1)) * 2"],
+                &["(hl0001=1 | hl0002=1) & hl0003=1", "hl0001 = (hl0002 + 1) * 2"],
+            ),
+            Code::MixedLogic => (
+                "'&' and '|' mixed without brackets",
+                "'&' binds stronger than '|', so 'a | b & c' is the same as 'a | (b & c)'. \
+                Brackets make the order explicit.",
+                &["hl0001=1 | hl0002=1 & hl0003=1"],
+                &["hl0001=1 | (hl0002=1 & hl0003=1)", "(hl0001=1 | hl0002=1) & hl0003=1"],
+            ),
+            Code::SingleElementList => (
+                "List with one element",
+                "A list with a single number, string or range is the same as the element. The \
+                parser does not create such lists, but syntax trees built in code or read from \
+                JSON may contain them.",
+                &[],
+                &["hl0001=1", "hl0001=1:3"],
+            ),
+            Code::UnknownItem => (
+                "Unknown item",
+                "With a metadatabase of item names, e.g. '--items FILE', items that are not in the \
+                metadatabase are reported. The closest known item is suggested. The examples assume \
+                the items hl0001 and hl0002.",
+                &["hl001=1", "any(hl0001..hl0003) = 1"],
+                &["hl0001=1", "any(hl0001..hl0002) = 1"],
+            ),
+            Code::CommentNotFormatted => (
+                "Comments can not be formatted",
+                "The formatter prints the syntax tree of a filter, which has no comments. Filters \
+                with comments are not formatted, so that the comments are not lost.",
+                &["hl0001=1 # adults only", "hl0001=1 /* adults */ & hl0002=1"],
+                &["hl0001=1"],
+            ),
+            Code::MixedListTypes => (
+                "List of strings and numbers",
+                "The elements of a list are all strings, or all numbers and ranges, as an item \
+                has either text or numeric values.",
+                &["pl0001='DE',1", "pl0001=1:3,'DE'"],
+                &["pl0001='DE','AT'", "pl0001=1:3,5"],
+            ),
+        };
+        Explanation { title, description, invalid, valid, warning: self.is_warning() }
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        writeln!(f, "{}\n\n{}", self.title, self.description)?;
+        let (invalid, valid) = if self.warning { ("Warning", "Preferred") } else { ("Invalid", "Valid") };
+        if !self.invalid.is_empty() {
+            writeln!(f, "\n{}:", invalid)?;
+            self.invalid.iter().try_for_each(|example| writeln!(f, "    {}", example.replace('\n', "\n    ")))?;
+        }
+        if !self.valid.is_empty() {
+            writeln!(f, "\n{}:", valid)?;
+            self.valid.iter().try_for_each(|example| writeln!(f, "    {}", example.replace('\n', "\n    ")))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::catalog::Catalog;
+    use crate::document::Document;
+    use crate::error::ParsingError;
+    use crate::scanner::Strictness;
+
+    /// Returns the errors or, for warnings, the warnings of an example, parsed as document or
+    /// filter as the code requires.
+    fn errors(code: Code, source: &str) -> Vec<ParsingError> {
+        match code {
+            Code::ExpectedFilterName | Code::DuplicateFilterName => Document::parse(source).errors,
+            Code::CommentNotFormatted => crate::formatter::format(source, &Default::default()).err().unwrap_or_default(),
+            Code::AlternativeOperator => crate::parse_with_strictness(source, Strictness::Strict).errors,
+            Code::UnknownItem => {
+                let catalog = Catalog::from_lines("hl0001\nhl0002");
+                crate::lint::lint_items(source, &crate::parse(source).expr.unwrap(), &catalog)
+            },
+            _ if code.is_warning() => {
+                let result = crate::parse(source);
+                assert!(result.errors.is_empty(), "Example of warning {} has errors: {:?}", code, source);
+                result.warnings
+            },
+            _ => crate::parse(source).errors,
+        }
+    }
+
+    #[test]
+    fn test_codes() {
+        assert_eq!(Code::MissingLogicalOperator.to_string(), "F0008");
+        assert_eq!(Code::from_str("F0008"), Ok(Code::MissingLogicalOperator));
+        assert!(Code::from_str("F9999").is_err());
+        assert!(Code::all().enumerate().all(|(index, code)| code.to_string() == format!("F{:04}", index)));
+    }
+
+    #[test]
+    fn test_explanation_examples() {
+        for code in Code::all().filter(|code| *code != Code::Internal) {
+            let explanation = code.explanation();
+            // Lists with one element can not be written in filters
+            let has_invalid = !explanation.invalid.is_empty() || code == Code::SingleElementList;
+            assert!(has_invalid && !explanation.valid.is_empty(), "Missing examples of {}", code);
+            for source in explanation.invalid {
+                let first = errors(code, source).first().and_then(ParsingError::code);
+                assert_eq!(first, Some(code), "Invalid example of {}: {:?}", code, source);
+            }
+            for source in explanation.valid {
+                assert!(errors(code, source).is_empty(), "Valid example of {}: {:?}", code, source);
+            }
+        }
+    }
+}
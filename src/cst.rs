@@ -0,0 +1,370 @@
+//! Lossless concrete syntax tree. Unlike `Expr`, the tree keeps all tokens as written, including
+//! whitespace and the kind of brackets, so that printing it reproduces the source exactly.
+//! Rewrites, such as renaming an item, only change the edited tokens.
+//!
+//! Ex.: Renaming "hl0001" in "q01;hl0001 = 1 & [hl0002=2]" gives "q01;hl0100 = 1 & [hl0002=2]"
+
+use std::fmt;
+
+use crate::catalog;
+use crate::error::ParsingError;
+use crate::expr::{Argument, Expr, Operand, Selector};
+use crate::scanner::{Literal, Numeric, Scanner, Token};
+use crate::span::Span;
+use crate::token_type::TokenType;
+
+/// Kinds of syntax nodes. They correspond to the variants of `Expr` and `Operand`. `Grouping`
+/// is used for both brackets around expressions and parentheses around arithmetic operands,
+/// `Call` for calls of both filter and number functions. `Selector` is an item, item range or
+/// item pattern of a quantifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    Grouping,
+    Not,
+    Logical,
+    Filter,
+    Item,
+    Number,
+    Text,
+    Range,
+    List,
+    Binary,
+    Negative,
+    Call,
+    Quantified,
+    Selector,
+}
+
+/// Inner node of the syntax tree. Children are nodes and tokens in source order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxNode {
+    pub kind: NodeKind,
+    pub children: Vec<SyntaxElement>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+/// Leaf of the syntax tree. `leading_trivia` holds the whitespace in front of the token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxToken {
+    pub kind: TokenType,
+    pub text: String,
+    pub literal: Option<Literal>,
+    pub leading_trivia: String,
+}
+
+/// Concrete syntax tree of a filter expression. `trailing_trivia` holds the whitespace after
+/// the last token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxTree {
+    pub root: SyntaxNode,
+    pub trailing_trivia: String,
+}
+
+/// Parses `source` into a lossless syntax tree.
+pub fn parse(source: &str) -> Result<SyntaxTree, Vec<ParsingError>> {
+    let expr = crate::parse(source).into_result()?;
+    let tokens: Vec<Token> = Scanner::new(source).filter_map(Result::ok).collect();
+
+    let mut builder = Builder { source, tokens: &tokens, position: 0, offset: 0 };
+    let root = builder.expr(&expr);
+    let trailing_trivia = source[builder.offset..].to_string();
+
+    Ok(SyntaxTree { root, trailing_trivia })
+}
+
+impl SyntaxTree {
+    /// Parses the tree back into an `Expr`.
+    pub fn to_expr(&self) -> Result<Expr, Vec<ParsingError>> {
+        crate::parse(&self.to_string()).into_result()
+    }
+
+    /// Renames all references to item `from`, including items of quantifiers. Question
+    /// identifiers, item ranges and item patterns are not changed. Returns the number of renamed
+    /// references.
+    pub fn rename_item(&mut self, from: &str, to: &str) -> usize {
+        let mut count = 0;
+        self.root.for_each_node_mut(&mut |node| {
+            if !matches!(node.kind, NodeKind::Item | NodeKind::Selector) || node.item() != Some(from) { return }
+            if let Some(SyntaxElement::Token(item)) = node.children.last_mut() {
+                item.text = to.to_string();
+                count += 1;
+            }
+        });
+        count
+    }
+
+    /// Replaces the value `from` by `to` in all filters on `item`, e.g. in lists and ranges, and
+    /// in quantifiers that select `item`. Returns the number of replaced values.
+    pub fn recode_value(&mut self, item: &str, from: Numeric, to: Numeric) -> usize {
+        let mut count = 0;
+        self.root.for_each_node_mut(&mut |node| {
+            let selected = match node.kind {
+                NodeKind::Filter => node.item() == Some(item),
+                NodeKind::Quantified => node.children.iter().any(|child| matches!(child, SyntaxElement::Node(selector) if selector.selects(item))),
+                _ => false,
+            };
+            if !selected { return }
+            for child in node.children.iter_mut().skip(1) {
+                if let SyntaxElement::Node(operand) = child
+                    && matches!(operand.kind, NodeKind::Number | NodeKind::Range | NodeKind::List) {
+                    operand.for_each_token_mut(&mut |token| {
+                        if token.literal == Some(Literal::Number(from)) {
+                            token.text = to.to_string();
+                            token.literal = Some(Literal::Number(to));
+                            count += 1;
+                        }
+                    });
+                }
+            }
+        });
+        count
+    }
+}
+
+impl SyntaxNode {
+    /// Name of the item referenced by an item node, a selector of a single item, or by the
+    /// left-hand side of a filter node.
+    pub fn item(&self) -> Option<&str> {
+        match self.kind {
+            NodeKind::Item => match self.children.last() {
+                Some(SyntaxElement::Token(token)) => Some(&token.text),
+                _ => None,
+            },
+            NodeKind::Selector => match self.children.as_slice() {
+                [.., SyntaxElement::Token(token)] if token.kind == TokenType::Identifier
+                    && !self.children.iter().any(|child| matches!(child, SyntaxElement::Token(token) if token.kind == TokenType::DotDot)) => Some(&token.text),
+                _ => None,
+            },
+            NodeKind::Filter => match self.children.first() {
+                Some(SyntaxElement::Node(node)) => node.item(),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Returns true if a selector node selects `item`, as single item, within an item range
+    /// or by an item pattern.
+    fn selects(&self, item: &str) -> bool {
+        if self.kind != NodeKind::Selector {
+            return false
+        }
+        let tokens: Vec<&SyntaxToken> = self.children.iter()
+            .filter_map(|child| match child { SyntaxElement::Token(token) => Some(token), _ => None })
+            .collect();
+        match tokens.as_slice() {
+            [.., start, dots, end] if dots.kind == TokenType::DotDot => {
+                catalog::item_range(&start.text, &end.text).is_ok_and(|items| items.iter().any(|name| name == item))
+            },
+            [.., pattern] if pattern.kind == TokenType::Pattern => item.starts_with(pattern.text.trim_end_matches('*')),
+            _ => self.item() == Some(item),
+        }
+    }
+
+    /// Calls `f` for this node and all nodes below it.
+    pub fn for_each_node_mut(&mut self, f: &mut impl FnMut(&mut SyntaxNode)) {
+        f(self);
+        for child in self.children.iter_mut() {
+            if let SyntaxElement::Node(node) = child {
+                node.for_each_node_mut(f);
+            }
+        }
+    }
+
+    /// Calls `f` for all tokens below this node.
+    pub fn for_each_token_mut(&mut self, f: &mut impl FnMut(&mut SyntaxToken)) {
+        for child in self.children.iter_mut() {
+            match child {
+                SyntaxElement::Node(node) => node.for_each_token_mut(f),
+                SyntaxElement::Token(token) => f(token),
+            }
+        }
+    }
+}
+
+impl fmt::Display for SyntaxTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{}{}", self.root, self.trailing_trivia)
+    }
+}
+
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        for child in self.children.iter() {
+            match child {
+                SyntaxElement::Node(node) => write!(f, "{}", node)?,
+                SyntaxElement::Token(token) => write!(f, "{}", token)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SyntaxToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{}{}", self.leading_trivia, self.text)
+    }
+}
+
+/// Child of an `Expr` or `Operand` node.
+enum Child<'a> {
+    Expr(&'a Expr),
+    Operand(&'a Operand),
+    Selector(&'a Selector),
+}
+
+impl Child<'_> {
+    fn span(&self) -> Span {
+        match self {
+            Child::Expr(expr) => expr.span(),
+            Child::Operand(operand) => operand.span(),
+            Child::Selector(selector) => selector.span(),
+        }
+    }
+}
+
+impl<'a> From<&'a Argument> for Child<'a> {
+    fn from(argument: &'a Argument) -> Self {
+        match argument {
+            Argument::Filter(expr) => Child::Expr(expr),
+            Argument::Operand(operand) => Child::Operand(operand),
+        }
+    }
+}
+
+/// Builds the syntax tree from the tokens and the spans of the parsed `Expr`. Tokens inside the
+/// span of a child expression belong to the child, all other tokens to the node itself.
+struct Builder<'a> {
+    source: &'a str,
+    tokens: &'a [Token<'a>],
+    position: usize, // index of next token
+    offset: usize,   // byte offset after previous token
+}
+
+impl Builder<'_> {
+    fn expr(&mut self, expr: &Expr) -> SyntaxNode {
+        let (kind, children) = match expr {
+            Expr::Grouping { expr, .. } => (NodeKind::Grouping, vec![Child::Expr(expr)]),
+            Expr::Not { expr, .. } => (NodeKind::Not, vec![Child::Expr(expr)]),
+            Expr::Logical { left, right, .. } => (NodeKind::Logical, vec![Child::Expr(left), Child::Expr(right)]),
+            Expr::Filter { left, right, .. } => (NodeKind::Filter, vec![Child::Operand(left), Child::Operand(right)]),
+            Expr::Call { arguments, .. } => (NodeKind::Call, arguments.iter().map(Child::from).collect()),
+            Expr::Quantified { items, right, .. } => {
+                let children = items.iter().map(Child::Selector).chain([Child::Operand(right)]).collect();
+                (NodeKind::Quantified, children)
+            },
+        };
+        self.node(kind, expr.span(), children)
+    }
+
+    fn operand(&mut self, operand: &Operand) -> SyntaxNode {
+        let (kind, children) = match operand {
+            Operand::Item { .. } => (NodeKind::Item, Vec::new()),
+            Operand::Number { .. } => (NodeKind::Number, Vec::new()),
+            Operand::Text { .. } => (NodeKind::Text, Vec::new()),
+            Operand::Range { .. } => (NodeKind::Range, Vec::new()),
+            Operand::List { .. } => (NodeKind::List, Vec::new()),
+            Operand::Binary { left, right, .. } => (NodeKind::Binary, vec![Child::Operand(left), Child::Operand(right)]),
+            Operand::Negative { operand, .. } => (NodeKind::Negative, vec![Child::Operand(operand)]),
+            Operand::Grouping { operand, .. } => (NodeKind::Grouping, vec![Child::Operand(operand)]),
+            Operand::Call { arguments, .. } => (NodeKind::Call, arguments.iter().map(Child::from).collect()),
+        };
+        self.node(kind, operand.span(), children)
+    }
+
+    fn node(&mut self, kind: NodeKind, span: Span, children: Vec<Child>) -> SyntaxNode {
+        let mut elements = Vec::new();
+        let mut children = children.into_iter().peekable();
+
+        while let Some(token) = self.tokens.get(self.position) {
+            if token.variant == TokenType::EOF || token.span.start >= span.end {
+                break
+            }
+            match children.next_if(|child| child.span().start == token.span.start) {
+                Some(Child::Expr(expr)) => elements.push(SyntaxElement::Node(self.expr(expr))),
+                Some(Child::Operand(operand)) => elements.push(SyntaxElement::Node(self.operand(operand))),
+                Some(Child::Selector(selector)) => elements.push(SyntaxElement::Node(self.node(NodeKind::Selector, selector.span(), Vec::new()))),
+                None => elements.push(SyntaxElement::Token(self.token())),
+            }
+        }
+
+        SyntaxNode { kind, children: elements }
+    }
+
+    fn token(&mut self) -> SyntaxToken {
+        let token = &self.tokens[self.position];
+        let syntax_token = SyntaxToken {
+            kind: token.variant.clone(),
+            text: token.lexeme.to_string(),
+            literal: token.literal.clone(),
+            leading_trivia: self.source[self.offset..token.span.start].to_string(),
+        };
+        self.position += 1;
+        self.offset = token.span.end;
+        syntax_token
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lossless() {
+        let cases = vec![
+            "q01;elb0001=1",
+            "  q01;hl0001 = -1,  -2 ,4 ",
+            "q01;hl0001=1&q02;hl0012=3 |\tq03;hl041=4",
+            "[q01;hl0001=1 & {q02;hl0012=3 | (q03;hl041 == 1:4)}]",
+            "((q01;hl0001=1))\n",
+            "!(q01;hl0001=1) & ! hl0002=1",
+            "hl0001 >= ( q01;hl0002+1 )*- hl0003",
+            "(q05;pl0001 - q05;pl0002) >= 18 & ( hl0010+1 = 2 )",
+            "pl0001 = 'DE' | pl0002 != \"it's\" ,'AT'",
+            "!missing( hl0001 ) & count(hl0010=1 , [hl0011=1]) >= abs(-2)",
+            "any( q01;hl0010 .. hl0025, hl01* ) = 1 & all(hl0001) != -1",
+        ];
+        for source in cases {
+            let tree = parse(source).unwrap();
+            assert_eq!(tree.to_string(), source);
+            assert!(tree.to_expr().unwrap().structurally_eq(&crate::parse(source).expr.unwrap()));
+        }
+        assert!(parse("q01;hl0001=").is_err());
+    }
+
+    #[test]
+    fn test_rewrite() {
+        let mut tree = parse("q01;hl0001 = 1 & [hl0002=hl0001 | hl0001== 1,2 ]").unwrap();
+        assert_eq!(tree.rename_item("hl0001", "hl0100"), 3);
+        assert_eq!(tree.to_string(), "q01;hl0100 = 1 & [hl0002=hl0100 | hl0100== 1,2 ]");
+
+        assert_eq!(tree.recode_value("hl0100", Numeric::Integer(1), Numeric::Integer(-1)), 2);
+        assert_eq!(tree.to_string(), "q01;hl0100 = -1 & [hl0002=hl0100 | hl0100== -1,2 ]");
+
+        let mut tree = parse("hl0001 = 1:3, 8 & hl0002 = 1").unwrap();
+        assert_eq!(tree.recode_value("hl0001", Numeric::Integer(3), Numeric::Integer(5)), 1);
+        assert_eq!(tree.recode_value("hl0001", Numeric::Integer(7), Numeric::Integer(5)), 0);
+        assert_eq!(tree.to_string(), "hl0001 = 1:5, 8 & hl0002 = 1");
+
+        // Items of quantifiers, but not ranges, patterns or questions
+        let mut tree = parse("any(hl0001, q01;hl0001 ) = 1 & all(hl0001..hl0003, hl0001*) != hl0001").unwrap();
+        assert_eq!(tree.rename_item("hl0001", "hl0100"), 3);
+        assert_eq!(tree.to_string(), "any(hl0100, q01;hl0100 ) = 1 & all(hl0001..hl0003, hl0001*) != hl0100");
+        assert!(tree.to_expr().is_ok());
+
+        // Quantifiers that select the item as single item, in a range or by a pattern
+        let source = "any(hl0001, hl0002) = 1 & all(hl0001..hl0003) != 1:2 & any(hl00*) = 1 & any(hl0002, q01;hl0003..hl0005) = 1";
+        let mut tree = parse(source).unwrap();
+        assert_eq!(tree.recode_value("hl0001", Numeric::Integer(1), Numeric::Integer(9)), 3);
+        assert_eq!(tree.to_string(), "any(hl0001, hl0002) = 9 & all(hl0001..hl0003) != 9:2 & any(hl00*) = 9 & any(hl0002, q01;hl0003..hl0005) = 1");
+        assert_eq!(tree.recode_value("hl0004", Numeric::Integer(1), Numeric::Integer(2)), 1);
+        assert!(tree.to_string().ends_with("any(hl0002, q01;hl0003..hl0005) = 2"));
+        assert!(tree.to_expr().is_ok());
+    }
+}
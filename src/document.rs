@@ -0,0 +1,190 @@
+//! Documents of named filters, e.g. filter files of a questionnaire. Every filter starts on a new
+//! line with its name, followed by ':' and the filter expression, which can span several lines:
+//!
+//! ```text
+//! # filters of the household questionnaire
+//! adults: hl0001 >= 18
+//! workers: q01;pl0001 = 1 &
+//!          q02;pl0002 = 1:3 // employed
+//! ```
+//!
+//! The whole document is scanned at once, so that tokens and errors keep their line and column
+//! in the document. The tokens of each filter are then parsed separately, so that an error in
+//! one filter does not affect the others.
+
+use std::collections::HashMap;
+
+use crate::code::Code;
+use crate::error::{ParsingError, SecondaryLabel};
+use crate::expr::{Expr, Ident};
+use crate::function::Registry;
+use crate::lint;
+use crate::parser::Parser;
+use crate::scanner::{Scanner, Token};
+use crate::span::Span;
+use crate::token_type::TokenType::*;
+
+/// Filter of a document with its name. `expr` is `None` if the filter could not be parsed.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NamedFilter {
+    pub name: Ident,
+    pub expr: Option<Expr>,
+    pub span: Span,
+}
+
+/// Result of parsing a document. Holds the filters in order of the source, all diagnostics
+/// reported by the scanner and the parser, and the warnings of all filters.
+#[derive(Debug, Clone)]
+pub struct Document {
+    pub filters: Vec<NamedFilter>,
+    pub errors: Vec<ParsingError>,
+    pub warnings: Vec<ParsingError>,
+}
+
+impl Document {
+    /// Parses a document of filters calling the built-in functions.
+    pub fn parse(source: &str) -> Self {
+        Self::parse_with_functions(source, &Registry::builtins())
+    }
+
+    /// Parses a document of filters calling the functions in `functions`.
+    pub fn parse_with_functions(source: &str, functions: &Registry) -> Self {
+        let mut results: Vec<_> = Scanner::new(source).collect();
+        results.retain(|result| !matches!(result, Ok(Token { variant: EOF, .. })));
+
+        let mut document = Document { filters: Vec::new(), errors: Vec::new(), warnings: Vec::new() };
+        let mut names = HashMap::new();
+        let mut index = 0;
+        let mut reported = false;
+
+        // Tokens before the first name
+        while index < results.len() && !starts_filter(&results, index) {
+            match &results[index] {
+                Ok(token) if !reported => {
+                    let (line, column, span) = (token.line, token.column, token.span);
+                    let message = "Expected filter name followed by ':'".to_string();
+                    document.errors.push(ParsingError::Report { code: Code::ExpectedFilterName, message, line, column, span, labels: Vec::new(), help: None });
+                    reported = true;
+                },
+                Ok(_) => {},
+                Err(error) => document.errors.push(error.clone()),
+            }
+            index += 1;
+        }
+
+        while index < results.len() {
+            let Ok(name) = &results[index] else { unreachable!("filters start with a name") };
+            let end = (index + 2..results.len()).find(|&next| starts_filter(&results, next)).unwrap_or(results.len());
+
+            if let Some(first) = names.get(name.lexeme) {
+                let message = format!("Filter '{}' is already defined", name.lexeme);
+                let labels = vec![SecondaryLabel { message: format!("'{}' is first defined here", name.lexeme), span: *first }];
+                document.errors.push(ParsingError::Report { code: Code::DuplicateFilterName, message, line: name.line, column: name.column, span: name.span, labels, help: None });
+            } else {
+                names.insert(name.lexeme, name.span);
+            }
+
+            // The name is followed by ':', so the filter has at least one token
+            let last = results[index + 1..end].iter().rev().find_map(|result| result.as_ref().ok()).unwrap_or(name);
+            let mut tokens = results[index + 2..end].to_vec();
+            tokens.push(Ok(end_of_filter(last)));
+
+            let mut parser = Parser::with_functions(tokens.into_iter(), functions.clone());
+            let expr = parser.parse().ok();
+            document.warnings.extend(parser.warnings());
+            let span = match &expr {
+                Some(expr) => {
+                    document.warnings.extend(lint::lint_expr(source, expr));
+                    name.span.to(expr.span())
+                },
+                None => name.span.to(last.span),
+            };
+            document.errors.extend(parser.into_errors().into_iter().filter(|error| matches!(error, ParsingError::Report { .. })));
+            document.filters.push(NamedFilter { name: Ident { name: name.lexeme.to_string(), span: name.span }, expr, span });
+            index = end;
+        }
+
+        lint::sort(&mut document.warnings);
+        document
+    }
+
+    /// Returns true if all filters were parsed without any diagnostics.
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty() && self.filters.iter().all(|filter| filter.expr.is_some())
+    }
+
+    /// Returns the filter named `name`.
+    pub fn get(&self, name: &str) -> Option<&NamedFilter> {
+        self.filters.iter().find(|filter| filter.name.name == name)
+    }
+}
+
+/// Returns true if the token at `index` is the name of a filter: an identifier at the start of
+/// a line, followed by ':'.
+fn starts_filter(results: &[Result<Token, ParsingError>], index: usize) -> bool {
+    let Some(Ok(token)) = results.get(index) else { return false };
+    let first_on_line = results[..index].iter().rev()
+        .find_map(|result| result.as_ref().ok())
+        .is_none_or(|previous| previous.line < token.line);
+    token.variant == Identifier && first_on_line && matches!(results.get(index + 1), Some(Ok(Token { variant: Colon, .. })))
+}
+
+/// Creates the EOF token of a filter, directly after its last token.
+fn end_of_filter<'a>(last: &Token<'a>) -> Token<'a> {
+    let column = last.column + last.lexeme.chars().count();
+    let span = Span::new(last.span.end, last.span.end);
+    Token { variant: EOF, lexeme: "", literal: None, line: last.line, column, span }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_document() {
+        let source = "# household\nadults: hl0001 >= 18\n\nworkers: q01;pl0001 = 1 &\n  /* employed */ q02;pl0002 = 1:3 // full or part time\nempty_name:hl0003=1";
+        let document = Document::parse(source);
+        assert!(document.is_ok(), "{:?}", document.errors);
+
+        let names: Vec<&str> = document.filters.iter().map(|filter| filter.name.name.as_str()).collect();
+        assert_eq!(names, vec!["adults", "workers", "empty_name"]);
+        assert!(document.warnings.is_empty());
+        let workers = document.get("workers").unwrap();
+        assert_eq!(workers.expr.as_ref().unwrap().to_string(), "q01;pl0001=1 & q02;pl0002=1:3");
+        assert_eq!(&source[std::ops::Range::from(workers.span)], "workers: q01;pl0001 = 1 &\n  /* employed */ q02;pl0002 = 1:3");
+    }
+
+    #[test]
+    fn test_document_errors() {
+        let cases = vec![
+            ("a: hl0001 = 1\nb: hl0002 =\nc: hl0003 = 1", "Expected number, string, list, range, or item (2:12)"),
+            ("a: hl0001 = 1 b: hl0002 = 1", "Expected '&' or '|' (1:15)"),
+            ("hl0001 = 1\na: hl0002 = 1", "Expected filter name followed by ':' (1:1)"),
+            ("a: hl0001 = 1\na: hl0002 = 1", "Filter 'a' is already defined (2:1)"),
+            ("a:\nb: hl0002 = 1", "Expected filter expression (1:3)"),
+            ("a: hl0001 = 1\nb: hl0002 = @", "Unexpected character (2:13)"),
+            ("a: hl0001 = 1 /*\nb: hl0002 = 1", "Unterminated block comment (1:15)"),
+        ];
+        for (source, expected) in cases {
+            let document = Document::parse(source);
+            assert!(!document.is_ok(), "Expected errors. Input: {}", source);
+            assert_eq!(document.errors[0].to_string(), expected, "Input: {}", source);
+        }
+
+        let document = Document::parse("a: hl0001==1 & hl0002=1\nb: hl0003==1 &\n  ((hl0004==1))");
+        let warnings: Vec<String> = document.warnings.iter().map(ParsingError::to_string).collect();
+        assert_eq!(warnings, vec!["'==' mixed with '=', use '=' (1:10)", "Redundant brackets (3:3)"]);
+
+        let source = "a: hl0001 = 1\na: hl0002 = 1";
+        let Some(ParsingError::Report { span, labels, .. }) = Document::parse(source).errors.first().cloned() else {
+            panic!("Expected reported error")
+        };
+        assert_eq!((span, labels[0].span), (Span::new(14, 15), Span::new(0, 1)));
+
+        // Errors do not affect the other filters
+        let document = Document::parse("a: hl0001 = 1\nb: hl0002 =\nc: hl0003 = 1");
+        let parsed: Vec<bool> = document.filters.iter().map(|filter| filter.expr.is_some()).collect();
+        assert_eq!(parsed, vec![true, false, true]);
+    }
+}
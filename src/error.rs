@@ -1,29 +1,56 @@
 use ariadne::{self, Label, Report, ReportKind, Source};
 use std::collections::HashSet;
 use std::fmt;
+use std::ops::Range;
+
+use crate::code::Code;
+use crate::span::Span;
 
 // Error handling. Consider using thiserror crate.
+/// Diagnostics of the scanner, the parser and lints. `line` and `column` give the position
+/// where the problem was detected, `span` the source text the diagnostic refers to, e.g. a
+/// token or a whole filter.
 #[derive(Debug, Clone)]
 pub enum ParsingError {
     Report {
+        code: Code,
         message: String,
         line: usize,
         column: usize,
+        span: Span,
+        labels: Vec<SecondaryLabel>,
+        help: Option<String>,
     },
     Internal {
         message: String,
         line: usize,
         column: usize,
     },
+    /// Valid, but suspicious source text. Warnings do not stop parsing.
+    Warning {
+        code: Code,
+        message: String,
+        line: usize,
+        column: usize,
+        span: Span,
+        help: Option<String>,
+    },
+}
+
+/// Related source text of an error, e.g. the unmatched '(' of "Expected ')'".
+#[derive(Debug, Clone, PartialEq)]
+pub struct SecondaryLabel {
+    pub message: String,
+    pub span: Span,
 }
 
 impl fmt::Display for ParsingError {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         match self {
-            ParsingError::Report { message, line, column } => {
+            ParsingError::Report { message, line, column, .. } => {
                 write!(f, "{} ({}:{})", message, line, column)
         },
-            ParsingError::Internal { message, line, column } => {
+            ParsingError::Internal { message, line, column } | ParsingError::Warning { message, line, column, .. } => {
                 write!(f, "{} ({}:{})", message, line, column)
             }
         }
@@ -32,48 +59,167 @@ impl fmt::Display for ParsingError {
 
 impl std::error::Error for ParsingError { }
 
+impl ParsingError {
+    /// Returns the code of a reported error or warning, or `None` for internal errors.
+    pub fn code(&self) -> Option<Code> {
+        match self {
+            ParsingError::Report { code, .. } | ParsingError::Warning { code, .. } => Some(*code),
+            ParsingError::Internal { .. } => None,
+        }
+    }
+
+    /// Returns the source text of a reported error or warning, or `None` for internal errors.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            ParsingError::Report { span, .. } | ParsingError::Warning { span, .. } => Some(*span),
+            ParsingError::Internal { .. } => None,
+        }
+    }
+
+    /// Returns the suggestion how to fix a reported error or warning, if there is one.
+    pub fn help(&self) -> Option<&str> {
+        match self {
+            ParsingError::Report { help, .. } | ParsingError::Warning { help, .. } => help.as_deref(),
+            ParsingError::Internal { .. } => None,
+        }
+    }
+
+    /// Adds a suggestion how to fix a reported error or warning, ex.: "Insert '&' or '|'".
+    pub fn with_help(mut self, suggestion: String) -> Self {
+        if let ParsingError::Report { help, .. } | ParsingError::Warning { help, .. } = &mut self {
+            *help = Some(suggestion);
+        }
+        self
+    }
+
+    pub fn is_warning(&self) -> bool {
+        matches!(self, ParsingError::Warning { .. })
+    }
+}
+
+
+/// Prints the errors and warnings of `source`, as separate reports with errors first.
+pub fn print_errors(source: &str, errors: &[ParsingError]) {
+    let (warnings, errors): (Vec<_>, Vec<_>) = errors.iter().cloned().partition(ParsingError::is_warning);
+    print_report(source, ReportKind::Error, "Parsing error", &errors);
+    print_report(source, ReportKind::Warning, "Parsing warning", &warnings);
+}
+
+fn print_report(source: &str, kind: ReportKind, message: &str, errors: &[ParsingError]) {
+    if let Some(report) = report(source, kind, message, errors) {
+        report.print((SOURCE_NAME, Source::from(source))).unwrap();
+    }
+}
+
+const SOURCE_NAME: &str = "CLI";
 
-pub fn print_errors(source: &str, errors: &Vec<ParsingError>) {
-    let source_name = "CLI";
-    let formatted_errors = format_errors(errors, source_name);
+/// Builds a report of `errors`, or `None` if there is nothing to report. The report is located
+/// at the first error, and suggestions of the errors are added as help.
+fn report<'a>(source: &str, kind: ReportKind<'a>, message: &str, errors: &[ParsingError]) -> Option<Report<'a, (&'static str, Range<usize>)>> {
+    let formatted_errors = format_errors(source, errors, SOURCE_NAME);
 
     if formatted_errors.is_empty() {
-        return
+        return None
     }
 
-    Report::build(ReportKind::Error, (source_name, 0..0))
-        .with_message("Parsing error")
-        .with_labels(formatted_errors)
-        .finish()
-        .print((source_name, Source::from(source)))
-        .unwrap();
+    let helps = errors.iter().filter_map(|error| Some(format!("[{}] {}", error.code()?, error.help()?)));
+    let location = errors.iter().find_map(ParsingError::span).map_or(0..0, |span| char_range(source, span));
+    let mut report = Report::build(kind, (SOURCE_NAME, location))
+        .with_message(message)
+        .with_labels(formatted_errors);
+    report.with_helps(helps);
+    let report = report.finish();
+    Some(report)
 }
 
-/// Converts a Vec<ParsingErros> into a Vec<ariadne::Label> which is used 
-/// to build a ariadne::Report.
-fn format_errors<'a>(errors: &Vec<ParsingError>, source_name: &'a str) -> Vec<ariadne::Label<(&'a str, std::ops::Range<usize>)>> {
+/// Converts a slice of ParsingErrors into a Vec<ariadne::Label> which is used 
+/// to build a ariadne::Report. Each error labels its span with a message starting with the code
+/// of the error, ex.: "[F0008]", followed by its secondary labels.
+fn format_errors<'a>(source: &str, errors: &[ParsingError], source_name: &'a str) -> Vec<ariadne::Label<(&'a str, std::ops::Range<usize>)>> {
     let mut formatted_errors = Vec::<Label<(&str, std::ops::Range<usize>)>>::new();
     let mut error_reported = HashSet::<(usize, usize)>::new();
 
     for error in errors.iter() {
-        match error {
-            ParsingError::Report {message, line, column} => {
-                // report only one error per (line, column) to declutter output
-                let pos = (*line, *column);
-                if !error_reported.contains(&pos) {
-                    let label = Label::new((source_name, *column-1..*column-1)).with_message(message); // -1 to
-                                                                                        // align 0-
-                                                                                        // and
-                                                                                        // 1-based
-                                                                                        // indexing
-                    formatted_errors.push(label);
-                    error_reported.insert(pos);
-                } 
-            },
-            ParsingError::Internal {..} => {},
+        let (code, message, line, column, span, labels) = match error {
+            ParsingError::Report { code, message, line, column, span, labels, .. } => (code, message, line, column, span, labels.as_slice()),
+            ParsingError::Warning { code, message, line, column, span, .. } => (code, message, line, column, span, [].as_slice()),
+            ParsingError::Internal { .. } => continue,
+        };
+
+        // report only one error per (line, column) to declutter output
+        if !error_reported.insert((*line, *column)) {
+            continue
+        }
+        formatted_errors.push(Label::new((source_name, char_range(source, *span))).with_message(format!("[{}] {}", code, message)));
+        for label in labels {
+            formatted_errors.push(Label::new((source_name, char_range(source, label.span))).with_message(&label.message));
         }
     }
     formatted_errors
 
 }
 
+/// Converts a span of byte offsets into the range of character offsets that ariadne expects.
+fn char_range(source: &str, span: Span) -> Range<usize> {
+    let offset = |byte: usize| source.get(..byte).map_or(byte, |before| before.chars().count());
+    offset(span.start)..offset(span.end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Renders the report of `source` without colors.
+    fn render(source: &str) -> String {
+        let result = crate::parse(source);
+        let errors = [result.errors, result.warnings].concat();
+        let mut output = Vec::new();
+        report(source, ReportKind::Error, "Parsing error", &errors).unwrap()
+            .write((SOURCE_NAME, Source::from(source)), &mut output)
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        // strip ANSI escape sequences
+        let mut plain = String::new();
+        let mut chars = output.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.by_ref().find(|&c| c == 'm');
+            } else {
+                plain.push(c);
+            }
+        }
+        plain
+    }
+
+    #[test]
+    fn test_char_range() {
+        let source = "hl0001 = \"äöü\" &\nhl0002 = @";
+        assert_eq!(char_range(source, Span::new(9, 17)), 9..14);
+        assert_eq!(char_range(source, Span::new(29, 30)), 26..27);
+        assert_eq!(char_range(source, Span::new(source.len(), source.len())), 27..27);
+    }
+
+    #[test]
+    fn test_multi_line_report() {
+        let cases = vec![
+            ("hl0001 = 1 &\nhl0002 = 1 &\nhl0003 =", "CLI:3:9", "3 │ hl0003 =", "[F0013] Expected number, string, list, range, or item"),
+            ("hl0001 = 1 &\n  hl0002 = 1\n  hl0003 = 1", "CLI:3:3", "3 │   hl0003 = 1", "[F0008] Expected '&' or '|'"),
+            ("hl0001 = \"ä\" &\nhl0002 = \"ö\" &\nhl0003 = @", "CLI:3:10", "3 │ hl0003 = @", "[F0001] Unexpected character"),
+            ("hl0001 = 1 & (\n  hl0002 = 1 |\n  hl0003 = 1", "CLI:3:13", "1 │ hl0001 = 1 & (", "Unclosed bracket"),
+        ];
+        for (source, expected_location, expected_line, expected_label) in cases {
+            let output = render(source);
+            let lines: Vec<&str> = output.lines().collect();
+            // the header is located at the first error
+            assert!(lines[1].contains(&format!("[ {} ]", expected_location)), "Expected {:?} in header of\n{}", expected_location, output);
+            let index = lines.iter().position(|line| line.trim_start().starts_with(expected_line))
+                .unwrap_or_else(|| panic!("Expected {:?} in\n{}", expected_line, output));
+            // labels are drawn below their source line, before the next numbered source line
+            let label = lines[index + 1..].iter()
+                .take_while(|line| !line.trim_start().starts_with(|c: char| c.is_ascii_digit()))
+                .any(|line| line.contains(expected_label));
+            assert!(label, "Expected {:?} below {:?} in\n{}", expected_label, expected_line, output);
+        }
+    }
+}
@@ -1,39 +1,724 @@
-use crate::scanner::Token;
+use std::fmt;
 
-#[derive(Debug, Clone)]
+use crate::formatter::{format_expr, format_operand, quote, Style};
+use crate::scanner::Numeric;
+use crate::span::Span;
+
+/// Node of the syntax tree. Every node carries the byte span of its full source text, from its
+/// first to its last token.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
 pub enum Expr {
+    /// Parenthesized expression. Ex.: "(q01;hl0001=1 | q02;hl0012=2)"
     Grouping {
         expr: Box<Expr>,
+        span: Span,
+    },
+    /// Negated expression. Ex.: "!(q01;hl0001=1 | q02;hl0012=2)"
+    Not {
+        expr: Box<Expr>,
+        span: Span,
     },
+    /// Two expressions combined with '&' or '|'.
     Logical {
         left: Box<Expr>,
-        operator: Token,
+        operator: Logic,
         right: Box<Expr>,
+        span: Span,
     },
-    Term {
-        expr: Box<Expr>,
+    /// A single comparison. Ex.: "q01;hl0001=1:3"
+    Filter {
+        left: Operand,
+        operator: Comparison,
+        right: Operand,
+        span: Span,
     },
-   Filter {
-        left: Box<Expr>,
-        operator: Token,
-        right: Box<Expr>,
-    }, 
-   Set {
-        question: Option<Token>,
-        item: Token,
-    }, 
-   Element,
-   Range {
-        left: Token,
-        right: Token,
+    /// Call of a function returning a boolean, used as a filter. Ex.: "missing(hl0001)"
+    Call {
+        name: Ident,
+        arguments: Vec<Argument>,
+        span: Span,
+    },
+    /// Comparison of a sequence of items. Ex.: "any(hl0010..hl0025) = 1" or "all(hl00*) != -1".
+    /// See `catalog::Catalog::expand` for the equivalent '|' or '&' chain.
+    Quantified {
+        quantifier: Quantifier,
+        items: Vec<Selector>,
+        operator: Comparison,
+        right: Operand,
+        span: Span,
+    },
+}
+
+impl Expr {
+    /// Returns the byte span of the source text covered by the expression.
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Grouping { span, .. }
+            | Expr::Not { span, .. }
+            | Expr::Logical { span, .. }
+            | Expr::Filter { span, .. }
+            | Expr::Call { span, .. }
+            | Expr::Quantified { span, .. } => *span,
+        }
+    }
+
+    /// Compares two expressions ignoring spans and groupings, i.e. only their logical and
+    /// arithmetic structure. `parse(&expr.to_string())` is structurally equal to `expr`.
+    pub fn structurally_eq(&self, other: &Expr) -> bool {
+        Canonical.fold_expr(self.clone()) == Canonical.fold_expr(other.clone())
+    }
+
+    /// Binding strength of the expression when printed without brackets.
+    pub(crate) fn precedence(&self) -> u8 {
+        match self {
+            Expr::Logical { operator: Logic::Or, .. } => 1,
+            Expr::Logical { operator: Logic::And, .. } => 2,
+            Expr::Not { .. } => 3,
+            Expr::Grouping { .. } | Expr::Filter { .. } | Expr::Call { .. } | Expr::Quantified { .. } => 4,
+        }
+    }
+}
+
+/// Prints the expression as filter text, e.g. "q01;hl0001=1 & (q02;hl0012=1:3 | hl0013=-1,-2)".
+/// Brackets are added where required by the precedence and left-associativity of '&' and '|'.
+/// Existing groupings are kept, see `formatter` for other styles.
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let style = Style { remove_redundant_groupings: false, ..Style::default() };
+        f.write_str(&format_expr(self, &style))
+    }
+}
+
+/// Side of a comparison.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
+pub enum Operand {
+    /// Reference to an item, optionally qualified by a question. Ex.: "q01;hl0001" or "hl0001"
+    Item {
+        question: Option<Ident>,
+        item: Ident,
+        span: Span,
+    },
+    /// Ex.: "1" or "-2.5"
+    Number {
+        value: Numeric,
+        span: Span,
     },
+    /// String literal, without quotes and with escapes resolved. Ex.: "DE" or 'DE'
+    Text {
+        value: String,
+        span: Span,
+    },
+    /// Ex.: "1:4", or open-ended "3:" and ":5"
+    Range {
+        start: Bound,
+        end: Bound,
+        span: Span,
+    },
+    /// Set of numbers and ranges. Ex.: "-2,-1,1" or "-2,-1,3:5,8"
     List {
-        value: Token,
-        next: Box<Expr>,
+        elements: Vec<Element>,
+        span: Span,
+    },
+    /// Arithmetic operation on numbers and items. Ex.: "q02;elb0002 / 12"
+    Binary {
+        left: Box<Operand>,
+        operator: Arithmetic,
+        right: Box<Operand>,
+        span: Span,
+    },
+    /// Negated arithmetic operand. Ex.: "-hl0001" or "-(hl0001 + 1)"
+    Negative {
+        operand: Box<Operand>,
+        span: Span,
+    },
+    /// Parenthesized arithmetic operand. Ex.: "(hl0001 + hl0002)"
+    Grouping {
+        operand: Box<Operand>,
+        span: Span,
+    },
+    /// Call of a function returning a number. Ex.: "abs(hl0001 - hl0002)"
+    Call {
+        name: Ident,
+        arguments: Vec<Argument>,
+        span: Span,
+    },
+}
+
+impl Operand {
+    /// Returns the byte span of the source text covered by the operand.
+    pub fn span(&self) -> Span {
+        match self {
+            Operand::Item { span, .. }
+            | Operand::Number { span, .. }
+            | Operand::Text { span, .. }
+            | Operand::Range { span, .. }
+            | Operand::List { span, .. }
+            | Operand::Binary { span, .. }
+            | Operand::Negative { span, .. }
+            | Operand::Grouping { span, .. }
+            | Operand::Call { span, .. } => *span,
+        }
+    }
+
+    /// Binding strength of the operand when printed without brackets.
+    pub(crate) fn precedence(&self) -> u8 {
+        match self {
+            Operand::Binary { operator: Arithmetic::Add | Arithmetic::Subtract, .. } => 1,
+            Operand::Binary { operator: Arithmetic::Multiply | Arithmetic::Divide, .. } => 2,
+            Operand::Negative { .. } => 3,
+            _ => 4,
+        }
+    }
+}
+
+/// Prints the operand as filter text, e.g. "q01;hl0001" or "(hl0001 + hl0002) / 2".
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let style = Style { remove_redundant_groupings: false, ..Style::default() };
+        f.write_str(&format_operand(self, &style))
+    }
+}
+
+/// Quantifiers of item sequences. `any` is true if the comparison holds for at least one item,
+/// `all` if it holds for every item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, strum_macros::Display)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Quantifier {
+    #[strum(serialize = "any")]
+    Any,
+    #[strum(serialize = "all")]
+    All,
+}
+
+/// Items compared by a quantifier. The question applies to every selected item.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
+pub enum Selector {
+    /// Ex.: "q01;hl0010"
+    Item {
+        question: Option<Ident>,
+        item: Ident,
+        span: Span,
+    },
+    /// Items with the same prefix and numbers from `start` to `end`. Ex.: "hl0010..hl0025"
+    Range {
+        question: Option<Ident>,
+        start: Ident,
+        end: Ident,
+        span: Span,
+    },
+    /// Items starting with the pattern before '*'. Ex.: "hl00*"
+    Pattern {
+        question: Option<Ident>,
+        pattern: Ident,
+        span: Span,
+    },
+}
+
+impl Selector {
+    /// Returns the byte span of the source text covered by the selector.
+    pub fn span(&self) -> Span {
+        match self {
+            Selector::Item { span, .. } | Selector::Range { span, .. } | Selector::Pattern { span, .. } => *span,
+        }
+    }
+
+    pub fn question(&self) -> Option<&Ident> {
+        match self {
+            Selector::Item { question, .. } | Selector::Range { question, .. } | Selector::Pattern { question, .. } => question.as_ref(),
+        }
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        if let Some(question) = self.question() {
+            write!(f, "{};", question)?;
+        }
+        match self {
+            Selector::Item { item, .. } => write!(f, "{}", item),
+            Selector::Range { start, end, .. } => write!(f, "{}..{}", start, end),
+            Selector::Pattern { pattern, .. } => write!(f, "{}", pattern),
+        }
+    }
+}
+
+/// Argument of a function call. The parser chooses the variant from the parameter type of the
+/// function, see `function::Registry`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Argument {
+    /// Ex.: "hl0001=1" in "count(hl0001=1, hl0002=1)"
+    Filter(Expr),
+    /// Ex.: "hl0001" in "missing(hl0001)"
+    Operand(Operand),
+}
+
+impl Argument {
+    /// Returns the byte span of the source text covered by the argument.
+    pub fn span(&self) -> Span {
+        match self {
+            Argument::Filter(expr) => expr.span(),
+            Argument::Operand(operand) => operand.span(),
+        }
+    }
+}
+
+/// Comparison operators. '=' and '==' are both parsed as `Comparison::Equal`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, strum_macros::Display)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Comparison {
+    #[strum(serialize = "=")]
+    #[cfg_attr(feature = "serde", serde(rename = "="))]
+    Equal,
+    #[strum(serialize = "!=")]
+    #[cfg_attr(feature = "serde", serde(rename = "!="))]
+    NotEqual,
+    #[strum(serialize = ">")]
+    #[cfg_attr(feature = "serde", serde(rename = ">"))]
+    Greater,
+    #[strum(serialize = ">=")]
+    #[cfg_attr(feature = "serde", serde(rename = ">="))]
+    GreaterEqual,
+    #[strum(serialize = "<")]
+    #[cfg_attr(feature = "serde", serde(rename = "<"))]
+    Less,
+    #[strum(serialize = "<=")]
+    #[cfg_attr(feature = "serde", serde(rename = "<="))]
+    LessEqual,
+}
+
+/// Arithmetic operators. '*' and '/' bind stronger than '+' and '-'.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, strum_macros::Display)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Arithmetic {
+    #[strum(serialize = "+")]
+    #[cfg_attr(feature = "serde", serde(rename = "+"))]
+    Add,
+    #[strum(serialize = "-")]
+    #[cfg_attr(feature = "serde", serde(rename = "-"))]
+    Subtract,
+    #[strum(serialize = "*")]
+    #[cfg_attr(feature = "serde", serde(rename = "*"))]
+    Multiply,
+    #[strum(serialize = "/")]
+    #[cfg_attr(feature = "serde", serde(rename = "/"))]
+    Divide,
+}
+
+/// Logical operators combining filters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, strum_macros::Display)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Logic {
+    #[strum(serialize = "&")]
+    #[cfg_attr(feature = "serde", serde(rename = "&"))]
+    And,
+    #[strum(serialize = "|")]
+    #[cfg_attr(feature = "serde", serde(rename = "|"))]
+    Or,
+}
+
+/// Name of a question, an item or a function together with its position in the source.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Ident {
+    pub name: String,
+    pub span: Span,
+}
+
+impl fmt::Display for Ident {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// A number together with its position in the source, e.g. an element of a list.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Value {
+    pub number: Numeric,
+    pub span: Span,
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{}", self.number)
+    }
+}
+
+/// Start or end of a range. Both bounds are inclusive, "3:5" contains 3, 4 and 5. An open
+/// bound has no number, ex.: the end of "3:" or the start of ":5". A range has at least one
+/// bounded side.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(untagged))]
+pub enum Bound {
+    Included(Value),
+    Unbounded,
+}
+
+impl Bound {
+    /// Returns the value of a bounded side.
+    pub fn value(&self) -> Option<&Value> {
+        match self {
+            Bound::Included(value) => Some(value),
+            Bound::Unbounded => None,
+        }
+    }
+}
+
+impl fmt::Display for Bound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Bound::Included(value) => write!(f, "{}", value),
+            Bound::Unbounded => Ok(()),
+        }
+    }
+}
+
+/// Element of a list. Ex.: "-1" or "3:5" in "-2,-1,3:5,8", or "DE" in "'DE','AT'"
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
+pub enum Element {
+    Value(Value),
+    Text {
+        value: String,
+        span: Span,
     },
-    EndOfList,
-    Literal {
-        value: Token, 
+    Range {
+        start: Bound,
+        end: Bound,
+        span: Span,
     },
 }
 
+impl Element {
+    /// Returns the byte span of the source text covered by the element.
+    pub fn span(&self) -> Span {
+        match self {
+            Element::Value(value) => value.span,
+            Element::Text { span, .. } => *span,
+            Element::Range { span, .. } => *span,
+        }
+    }
+}
+
+impl fmt::Display for Element {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Element::Value(value) => write!(f, "{}", value),
+            Element::Text { value, .. } => f.write_str(&quote(value)),
+            Element::Range { start, end, .. } => write!(f, "{}:{}", start, end),
+        }
+    }
+}
+
+
+/// Read-only traversal of a syntax tree. Default methods visit all children, so implementors
+/// only override the methods for the nodes they are interested in.
+///
+/// Ex.: Collect all referenced items
+/// ```
+/// use filter_parser::expr::{Ident, Operand, Visitor, walk_operand};
+///
+/// struct Items(Vec<String>);
+///
+/// impl Visitor for Items {
+///     fn visit_operand(&mut self, operand: &Operand) {
+///         if let Operand::Item { item, .. } = operand {
+///             self.0.push(item.name.clone());
+///         }
+///         walk_operand(self, operand);
+///     }
+/// }
+///
+/// let expr = filter_parser::parse("q01;hl0001=1 & (hl0002=hl0003 | hl0004=1:3)").expr.unwrap();
+/// let mut items = Items(Vec::new());
+/// items.visit_expr(&expr);
+/// assert_eq!(items.0, vec!["hl0001", "hl0002", "hl0003", "hl0004"]);
+/// ```
+pub trait Visitor {
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr);
+    }
+
+    fn visit_operand(&mut self, operand: &Operand) {
+        walk_operand(self, operand);
+    }
+
+    fn visit_ident(&mut self, _ident: &Ident) {}
+
+    fn visit_value(&mut self, _value: &Value) {}
+}
+
+/// Visits the children of `expr`.
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expr) {
+    match expr {
+        Expr::Grouping { expr, .. } | Expr::Not { expr, .. } => visitor.visit_expr(expr),
+        Expr::Logical { left, right, .. } => {
+            visitor.visit_expr(left);
+            visitor.visit_expr(right);
+        },
+        Expr::Filter { left, right, .. } => {
+            visitor.visit_operand(left);
+            visitor.visit_operand(right);
+        },
+        Expr::Call { arguments, .. } => walk_arguments(visitor, arguments),
+        Expr::Quantified { items, right, .. } => {
+            for selector in items {
+                selector.question().into_iter().for_each(|question| visitor.visit_ident(question));
+                match selector {
+                    Selector::Item { item, .. } => visitor.visit_ident(item),
+                    Selector::Range { start, end, .. } => {
+                        visitor.visit_ident(start);
+                        visitor.visit_ident(end);
+                    },
+                    Selector::Pattern { pattern, .. } => visitor.visit_ident(pattern),
+                }
+            }
+            visitor.visit_operand(right);
+        },
+    }
+}
+
+/// Visits the children of `operand`.
+pub fn walk_operand<V: Visitor + ?Sized>(visitor: &mut V, operand: &Operand) {
+    match operand {
+        Operand::Item { question, item, .. } => {
+            if let Some(question) = question {
+                visitor.visit_ident(question);
+            }
+            visitor.visit_ident(item);
+        },
+        Operand::Number { value, span } => visitor.visit_value(&Value { number: *value, span: *span }),
+        Operand::Text { .. } => {},
+        Operand::Range { start, end, .. } => {
+            start.value().into_iter().chain(end.value()).for_each(|value| visitor.visit_value(value));
+        },
+        Operand::List { elements, .. } => {
+            for element in elements {
+                match element {
+                    Element::Value(value) => visitor.visit_value(value),
+                    Element::Range { start, end, .. } => {
+                        start.value().into_iter().chain(end.value()).for_each(|value| visitor.visit_value(value));
+                    },
+                    Element::Text { .. } => {},
+                }
+            }
+        },
+        Operand::Binary { left, right, .. } => {
+            visitor.visit_operand(left);
+            visitor.visit_operand(right);
+        },
+        Operand::Negative { operand, .. } | Operand::Grouping { operand, .. } => visitor.visit_operand(operand),
+        Operand::Call { arguments, .. } => walk_arguments(visitor, arguments),
+    }
+}
+
+/// Visits the arguments of a call. Function names are not visited as identifiers.
+fn walk_arguments<V: Visitor + ?Sized>(visitor: &mut V, arguments: &[Argument]) {
+    for argument in arguments {
+        match argument {
+            Argument::Filter(expr) => visitor.visit_expr(expr),
+            Argument::Operand(operand) => visitor.visit_operand(operand),
+        }
+    }
+}
+
+/// Rewrites a syntax tree by consuming it and building a new one. Default methods rebuild
+/// every node from its folded children, so implementors only override the methods for the
+/// nodes they want to change.
+///
+/// Ex.: Rename an item
+/// ```
+/// use filter_parser::expr::{Fold, Ident};
+///
+/// struct Rename;
+///
+/// impl Fold for Rename {
+///     fn fold_ident(&mut self, ident: Ident) -> Ident {
+///         if ident.name == "hl0001" { Ident { name: "hl0100".to_string(), ..ident } } else { ident }
+///     }
+/// }
+///
+/// let expr = filter_parser::parse("q01;hl0001=1 & hl0002=hl0001").expr.unwrap();
+/// assert_eq!(Rename.fold_expr(expr).to_string(), "q01;hl0100=1 & hl0002=hl0100");
+/// ```
+pub trait Fold {
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        fold_expr(self, expr)
+    }
+
+    fn fold_operand(&mut self, operand: Operand) -> Operand {
+        fold_operand(self, operand)
+    }
+
+    fn fold_ident(&mut self, ident: Ident) -> Ident {
+        Ident { span: self.fold_span(ident.span), ..ident }
+    }
+
+    fn fold_value(&mut self, value: Value) -> Value {
+        Value { span: self.fold_span(value.span), ..value }
+    }
+
+    fn fold_span(&mut self, span: Span) -> Span {
+        span
+    }
+}
+
+/// Rebuilds `expr` from its folded children.
+pub fn fold_expr<F: Fold + ?Sized>(folder: &mut F, expr: Expr) -> Expr {
+    match expr {
+        Expr::Grouping { expr, span } => Expr::Grouping {
+            expr: Box::new(folder.fold_expr(*expr)),
+            span: folder.fold_span(span),
+        },
+        Expr::Not { expr, span } => Expr::Not {
+            expr: Box::new(folder.fold_expr(*expr)),
+            span: folder.fold_span(span),
+        },
+        Expr::Logical { left, operator, right, span } => Expr::Logical {
+            left: Box::new(folder.fold_expr(*left)),
+            operator,
+            right: Box::new(folder.fold_expr(*right)),
+            span: folder.fold_span(span),
+        },
+        Expr::Filter { left, operator, right, span } => Expr::Filter {
+            left: folder.fold_operand(left),
+            operator,
+            right: folder.fold_operand(right),
+            span: folder.fold_span(span),
+        },
+        Expr::Call { name, arguments, span } => Expr::Call {
+            name: Ident { span: folder.fold_span(name.span), ..name },
+            arguments: fold_arguments(folder, arguments),
+            span: folder.fold_span(span),
+        },
+        Expr::Quantified { quantifier, items, operator, right, span } => Expr::Quantified {
+            quantifier,
+            items: items.into_iter().map(|selector| fold_selector(folder, selector)).collect(),
+            operator,
+            right: folder.fold_operand(right),
+            span: folder.fold_span(span),
+        },
+    }
+}
+
+fn fold_selector<F: Fold + ?Sized>(folder: &mut F, selector: Selector) -> Selector {
+    match selector {
+        Selector::Item { question, item, span } => Selector::Item {
+            question: question.map(|question| folder.fold_ident(question)),
+            item: folder.fold_ident(item),
+            span: folder.fold_span(span),
+        },
+        Selector::Range { question, start, end, span } => Selector::Range {
+            question: question.map(|question| folder.fold_ident(question)),
+            start: folder.fold_ident(start),
+            end: folder.fold_ident(end),
+            span: folder.fold_span(span),
+        },
+        Selector::Pattern { question, pattern, span } => Selector::Pattern {
+            question: question.map(|question| folder.fold_ident(question)),
+            pattern: folder.fold_ident(pattern),
+            span: folder.fold_span(span),
+        },
+    }
+}
+
+/// Rebuilds `operand` from its folded children.
+pub fn fold_operand<F: Fold + ?Sized>(folder: &mut F, operand: Operand) -> Operand {
+    match operand {
+        Operand::Item { question, item, span } => Operand::Item {
+            question: question.map(|question| folder.fold_ident(question)),
+            item: folder.fold_ident(item),
+            span: folder.fold_span(span),
+        },
+        Operand::Number { value, span } => {
+            let value = folder.fold_value(Value { number: value, span });
+            Operand::Number { value: value.number, span: value.span }
+        },
+        Operand::Text { value, span } => Operand::Text { value, span: folder.fold_span(span) },
+        Operand::Range { start, end, span } => Operand::Range {
+            start: fold_bound(folder, start),
+            end: fold_bound(folder, end),
+            span: folder.fold_span(span),
+        },
+        Operand::List { elements, span } => Operand::List {
+            elements: elements.into_iter().map(|element| match element {
+                Element::Value(value) => Element::Value(folder.fold_value(value)),
+                Element::Text { value, span } => Element::Text { value, span: folder.fold_span(span) },
+                Element::Range { start, end, span } => Element::Range {
+                    start: fold_bound(folder, start),
+                    end: fold_bound(folder, end),
+                    span: folder.fold_span(span),
+                },
+            }).collect(),
+            span: folder.fold_span(span),
+        },
+        Operand::Binary { left, operator, right, span } => Operand::Binary {
+            left: Box::new(folder.fold_operand(*left)),
+            operator,
+            right: Box::new(folder.fold_operand(*right)),
+            span: folder.fold_span(span),
+        },
+        Operand::Negative { operand, span } => Operand::Negative {
+            operand: Box::new(folder.fold_operand(*operand)),
+            span: folder.fold_span(span),
+        },
+        Operand::Grouping { operand, span } => Operand::Grouping {
+            operand: Box::new(folder.fold_operand(*operand)),
+            span: folder.fold_span(span),
+        },
+        Operand::Call { name, arguments, span } => Operand::Call {
+            name: Ident { span: folder.fold_span(name.span), ..name },
+            arguments: fold_arguments(folder, arguments),
+            span: folder.fold_span(span),
+        },
+    }
+}
+
+/// Rebuilds the arguments of a call. Function names are not folded as identifiers.
+fn fold_arguments<F: Fold + ?Sized>(folder: &mut F, arguments: Vec<Argument>) -> Vec<Argument> {
+    arguments.into_iter().map(|argument| match argument {
+        Argument::Filter(expr) => Argument::Filter(folder.fold_expr(expr)),
+        Argument::Operand(operand) => Argument::Operand(folder.fold_operand(operand)),
+    }).collect()
+}
+
+fn fold_bound<F: Fold + ?Sized>(folder: &mut F, bound: Bound) -> Bound {
+    match bound {
+        Bound::Included(value) => Bound::Included(folder.fold_value(value)),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+/// Removes groupings and resets all spans, leaving only the logical structure of a tree.
+struct Canonical;
+
+impl Fold for Canonical {
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        match expr {
+            Expr::Grouping { expr, .. } => self.fold_expr(*expr),
+            _ => fold_expr(self, expr),
+        }
+    }
+
+    fn fold_operand(&mut self, operand: Operand) -> Operand {
+        match operand {
+            Operand::Grouping { operand, .. } => self.fold_operand(*operand),
+            _ => fold_operand(self, operand),
+        }
+    }
+
+    fn fold_span(&mut self, _span: Span) -> Span {
+        Span::default()
+    }
+}
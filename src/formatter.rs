@@ -0,0 +1,383 @@
+//! Formatter for filter expressions. Normalizes spacing, brackets and operators of a parsed
+//! filter according to a configurable `Style`.
+//!
+//! Ex.: "q01;hl0001 == 1&[q02;hl0012=1 ,2]" -> "q01;hl0001=1 & (q02;hl0012=1,2)"
+
+use crate::code::Code;
+use crate::error::ParsingError;
+use crate::expr::{Argument, Arithmetic, Expr, Ident, Logic, Operand};
+use crate::lint;
+use crate::scanner::Scanner;
+use crate::span::Span;
+
+/// Kind of brackets used for groupings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bracket {
+    Round,  // ()
+    Square, // []
+    Curly,  // {}
+}
+
+impl Bracket {
+    fn open(&self) -> char {
+        match self {
+            Bracket::Round => '(',
+            Bracket::Square => '[',
+            Bracket::Curly => '{',
+        }
+    }
+
+    fn close(&self) -> char {
+        match self {
+            Bracket::Round => ')',
+            Bracket::Square => ']',
+            Bracket::Curly => '}',
+        }
+    }
+}
+
+/// Formatting options. The default style gives "q01;hl0001=1,2 & (q02;hl0012=1 | hl0013=1:3)"
+/// and "count(a=1,b=1)>=hl0001 + 1".
+#[derive(Debug, Clone, PartialEq)]
+pub struct Style {
+    /// Brackets used for all groupings.
+    pub bracket: Bracket,
+    /// Spaces around comparison operators: "hl0001 = 1" instead of "hl0001=1".
+    pub space_around_comparison: bool,
+    /// Spaces around '&' and '|': "a=1 & b=1" instead of "a=1&b=1".
+    pub space_around_logic: bool,
+    /// Spaces around '+', '-', '*' and '/': "hl0001 + 1" instead of "hl0001+1".
+    pub space_around_arithmetic: bool,
+    /// Space after commas in lists, call arguments and quantifier selectors: "1, 2, 3" instead
+    /// of "1,2,3".
+    pub space_after_comma: bool,
+    /// Removes brackets that do not change the structure of the filter, e.g. "((a=1))" or
+    /// "(a=1 | b=1) | c=1". Brackets around '&' within '|' are kept or added, see lint F0031.
+    pub remove_redundant_groupings: bool,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            bracket: Bracket::Round,
+            space_around_comparison: false,
+            space_around_logic: true,
+            space_around_arithmetic: true,
+            space_after_comma: false,
+            remove_redundant_groupings: true,
+        }
+    }
+}
+
+/// Parses and formats a filter expression. Filters with comments are not formatted, as the
+/// syntax tree has no comments.
+pub fn format(source: &str, style: &Style) -> Result<String, Vec<ParsingError>> {
+    let expr = crate::parse(source).into_result()?;
+    if let Some(span) = comment(source) {
+        let (line, column) = lint::position(source, span.start);
+        let message = "Comments can not be formatted".to_string();
+        return Err(vec![ParsingError::Report { code: Code::CommentNotFormatted, message, line, column, span, labels: Vec::new(), help: None }])
+    }
+    Ok(format_expr(&expr, style))
+}
+
+/// Returns the span of the first comment of `source`, i.e. of text between tokens other than
+/// whitespace.
+fn comment(source: &str) -> Option<Span> {
+    let mut end = 0;
+    for token in Scanner::new(source).filter_map(Result::ok) {
+        let gap = &source[end..token.span.start];
+        let text = gap.trim();
+        if !text.is_empty() {
+            let start = end + gap.len() - gap.trim_start().len();
+            return Some(Span::new(start, start + text.len()))
+        }
+        end = token.span.end;
+    }
+    None
+}
+
+/// Returns true if formatting `source` does not change it. Used for check-only runs.
+pub fn is_formatted(source: &str, style: &Style) -> Result<bool, Vec<ParsingError>> {
+    Ok(format(source, style)? == source)
+}
+
+/// Formats a syntax tree. Brackets are added where required by precedence and
+/// left-associativity of operators, so the output parses back into the same structure.
+pub fn format_expr(expr: &Expr, style: &Style) -> String {
+    let mut formatter = Formatter { style, output: String::new() };
+    let expr = if style.remove_redundant_groupings { unwrap_groupings(expr) } else { expr };
+    formatter.expr(expr);
+    formatter.output
+}
+
+/// Formats an operand of a filter, e.g. "q01;hl0001" or "(hl0001 + hl0002) / 2".
+pub fn format_operand(operand: &Operand, style: &Style) -> String {
+    let mut formatter = Formatter { style, output: String::new() };
+    let operand = if style.remove_redundant_groupings { unwrap_operand_groupings(operand) } else { operand };
+    formatter.operand(operand);
+    formatter.output
+}
+
+struct Formatter<'a> {
+    style: &'a Style,
+    output: String,
+}
+
+impl Formatter<'_> {
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Grouping { expr, .. } => self.grouping(expr),
+            Expr::Not { expr: operand, .. } => {
+                self.output.push('!');
+                self.operand_expr(operand, |child| child.precedence() < expr.precedence());
+            },
+            Expr::Logical { left, operator, right, .. } => {
+                let precedence = expr.precedence();
+                // '&' within '|' gets brackets when groupings are normalized, although '&' binds
+                // stronger. Otherwise the groupings of the tree are kept as they are.
+                let mixed = |child: &Expr| self.style.remove_redundant_groupings && *operator == Logic::Or
+                    && matches!(child, Expr::Logical { operator: Logic::And, .. });
+                self.operand_expr(left, |child| child.precedence() < precedence || mixed(child));
+                self.operator(&operator.to_string(), self.style.space_around_logic);
+                self.operand_expr(right, |child| child.precedence() <= precedence || mixed(child));
+            },
+            Expr::Filter { left, operator, right, .. } => {
+                self.arithmetic_operand(left, |_| false);
+                self.operator(&operator.to_string(), self.style.space_around_comparison);
+                self.arithmetic_operand(right, |_| false);
+            },
+            Expr::Call { name, arguments, .. } => self.call(name, arguments),
+            Expr::Quantified { quantifier, items, operator, right, .. } => {
+                let items: Vec<String> = items.iter().map(|selector| selector.to_string()).collect();
+                self.output.push_str(&format!("{}({})", quantifier, items.join(self.comma())));
+                self.operator(&operator.to_string(), self.style.space_around_comparison);
+                self.arithmetic_operand(right, |_| false);
+            },
+        }
+    }
+
+    /// Writes an operand of '!', '&' or '|', adding brackets if `needs_brackets` is true.
+    fn operand_expr(&mut self, expr: &Expr, needs_brackets: impl Fn(&Expr) -> bool) {
+        let expr = if self.style.remove_redundant_groupings { unwrap_groupings(expr) } else { expr };
+        if needs_brackets(expr) {
+            self.grouping(expr);
+        } else {
+            self.expr(expr);
+        }
+    }
+
+    fn grouping(&mut self, expr: &Expr) {
+        self.output.push(self.style.bracket.open());
+        self.expr(expr);
+        self.output.push(self.style.bracket.close());
+    }
+
+    fn operator(&mut self, operator: &str, spaced: bool) {
+        if spaced {
+            self.output.push(' ');
+            self.output.push_str(operator);
+            self.output.push(' ');
+        } else {
+            self.output.push_str(operator);
+        }
+    }
+
+    fn operand(&mut self, operand: &Operand) {
+        match operand {
+            Operand::Item { question, item, .. } => {
+                if let Some(question) = question {
+                    self.output.push_str(&question.name);
+                    self.output.push(';');
+                }
+                self.output.push_str(&item.name);
+            },
+            Operand::Number { value, .. } => self.output.push_str(&value.to_string()),
+            Operand::Text { value, .. } => self.output.push_str(&quote(value)),
+            Operand::Range { start, end, .. } => self.output.push_str(&format!("{}:{}", start, end)),
+            Operand::List { elements, .. } => {
+                let elements: Vec<String> = elements.iter().map(|element| element.to_string()).collect();
+                self.output.push_str(&elements.join(self.comma()));
+            },
+            Operand::Binary { left, operator, right, .. } => {
+                let precedence = operand.precedence();
+                // "q01-hl0001" is reported as a mistyped "q01;hl0001", so '-' after an item keeps
+                // its spaces unless a number or bracket follows
+                let ambiguous = *operator == Arithmetic::Subtract && matches!(unwrap_operand_groupings(left), Operand::Item { .. })
+                    && !matches!(unwrap_operand_groupings(right), Operand::Number { .. } | Operand::Negative { .. } | Operand::Grouping { .. });
+                self.arithmetic_operand(left, |child| child.precedence() < precedence);
+                self.operator(&operator.to_string(), self.style.space_around_arithmetic || ambiguous);
+                self.arithmetic_operand(right, |child| child.precedence() <= precedence);
+            },
+            Operand::Negative { operand: inner, .. } => {
+                // "-1" is parsed as a negative number, so a negated number keeps its brackets
+                self.output.push('-');
+                self.arithmetic_operand(inner, |child| child.precedence() < operand.precedence() || matches!(child, Operand::Number { .. }));
+            },
+            Operand::Grouping { operand, .. } => self.operand_grouping(operand),
+            Operand::Call { name, arguments, .. } => self.call(name, arguments),
+        }
+    }
+
+    /// Separator of list elements, call arguments and quantifier selectors.
+    fn comma(&self) -> &'static str {
+        if self.style.space_after_comma { ", " } else { "," }
+    }
+
+    /// Writes a function call.
+    fn call(&mut self, name: &Ident, arguments: &[Argument]) {
+        self.output.push_str(&name.name);
+        self.output.push('(');
+        for (index, argument) in arguments.iter().enumerate() {
+            if index > 0 {
+                self.output.push_str(self.comma());
+            }
+            match argument {
+                Argument::Filter(expr) => self.operand_expr(expr, |_| false),
+                Argument::Operand(operand) => self.arithmetic_operand(operand, |_| false),
+            }
+        }
+        self.output.push(')');
+    }
+
+    /// Writes an operand of an arithmetic or comparison operator, adding parentheses if
+    /// `needs_brackets` is true. Arithmetic always uses round brackets.
+    fn arithmetic_operand(&mut self, operand: &Operand, needs_brackets: impl Fn(&Operand) -> bool) {
+        let operand = if self.style.remove_redundant_groupings { unwrap_operand_groupings(operand) } else { operand };
+        if needs_brackets(operand) {
+            self.operand_grouping(operand);
+        } else {
+            self.operand(operand);
+        }
+    }
+
+    fn operand_grouping(&mut self, operand: &Operand) {
+        self.output.push('(');
+        self.operand(operand);
+        self.output.push(')');
+    }
+}
+
+/// Writes a string literal in double quotes, escaping quotes, backslashes, newlines and tabs.
+pub(crate) fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Strips all groupings around an expression, e.g. "((a=1 | b=1))" -> "a=1 | b=1".
+fn unwrap_groupings(mut expr: &Expr) -> &Expr {
+    while let Expr::Grouping { expr: inner, .. } = expr {
+        expr = inner;
+    }
+    expr
+}
+
+/// Strips all parentheses around an operand, e.g. "((hl0001 + 1))" -> "hl0001 + 1".
+fn unwrap_operand_groupings(mut operand: &Operand) -> &Operand {
+    while let Operand::Grouping { operand: inner, .. } = operand {
+        operand = inner;
+    }
+    operand
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let style = Style::default();
+        let cases = vec![
+            ("q01;hl0001=1&q02;hl0012=3", "q01;hl0001=1 & q02;hl0012=3"),
+            ("q01;hl0001 = 1 , 2", "q01;hl0001=1,2"),
+            ("q01;hl0001 == 1", "q01;hl0001=1"),
+            ("[q01;hl0001=1 & {q02;hl0012=3 | q03;hl041=4}]", "q01;hl0001=1 & (q02;hl0012=3 | q03;hl041=4)"),
+            ("((q01;hl0001=1))", "q01;hl0001=1"),
+            ("a=1 | (b=1 & c=1)", "a=1 | (b=1 & c=1)"),
+            ("a=1 & b=1 | c=1", "(a=1 & b=1) | c=1"),
+            ("(a=1 | b=1) | c=1", "a=1 | b=1 | c=1"),
+            ("a=1 | (b=1 | c=1)", "a=1 | (b=1 | c=1)"),
+            ("a=1 & ((b=1 | c=1))", "a=1 & (b=1 | c=1)"),
+            ("!(a=1) & !{b=1 | c=1}", "!a=1 & !(b=1 | c=1)"),
+            ("!!(a=1 & b=1)", "!!(a=1 & b=1)"),
+            ("a=b-1", "a=b - 1"),
+            ("a=((b+1))*2 & c=(1*2)+3", "a=(b + 1) * 2 & c=1 * 2 + 3"),
+            ("a=b-(c-1)", "a=b - (c - 1)"),
+            ("a=-(b*2) | a=- 1", "a=-(b * 2) | a=-1"),
+            ("(a+b)/2>40 & ((a*2=b))", "(a + b) / 2>40 & a * 2=b"),
+            ("a = ((1)) | ((a))=1 | any(a) = (b + 1)", "a=1 | a=1 | any(a)=b + 1"),
+            ("a = -2, -1 ,3 : 5,8", "a=-2,-1,3:5,8"),
+            ("a = : -1 , 8 :", "a=:-1,8:"),
+            (r#"a = 'DE' | b != 'it\'s', "say \"hi\"""#, r#"a="DE" | b!="it's","say \"hi\"""#),
+            ("!missing( a ) & count((a=1 | b=1),c=1:3)>=max(d,(e+1))", "!missing(a) & count(a=1 | b=1,c=1:3)>=max(d,e + 1)"),
+            ("any( q01;a1..a3 ,b* )== 1 | all(c)!=-1", "any(q01;a1..a3,b*)=1 | all(c)!=-1"),
+        ];
+        for (source, expected) in cases {
+            let formatted = format(source, &style).unwrap();
+            assert_eq!(formatted, expected, "Input: {}", source);
+            assert!(is_formatted(&formatted, &style).unwrap());
+            assert!(crate::parse(&formatted).warnings.is_empty(), "Warnings of {:?}", formatted);
+            assert!(crate::parse(&formatted).expr.unwrap().structurally_eq(&crate::parse(source).expr.unwrap()));
+        }
+        assert!(!is_formatted("q01;hl0001 = 1", &style).unwrap());
+        assert!(format("q01;hl0001 =", &style).is_err());
+
+        let errors = format("hl0001=1 & // adults only\n  hl0002=1", &style).unwrap_err();
+        assert_eq!(errors[0].to_string(), "Comments can not be formatted (1:12)");
+        assert_eq!(errors[0].span(), Some(Span::new(11, 25)));
+    }
+
+    #[test]
+    fn test_format_round_trip() {
+        let style = Style::default();
+        for source in ["hl0001 = -(1)", "hl0001 = -((-2.5))", "hl0001 = -1", "hl0001 = --hl0002", "hl0001 = -(1) * 2"] {
+            let expr = crate::parse(source).expr.unwrap();
+            let formatted = format_expr(&expr, &style);
+            let reparsed = crate::parse(&formatted).expr.unwrap();
+            assert!(reparsed.structurally_eq(&expr), "Round trip failed for {}: {}", source, formatted);
+        }
+        assert_eq!(format("hl0001 = -(1)", &style).unwrap(), "hl0001=-(1)");
+    }
+
+    #[test]
+    fn test_format_style() {
+        let style = Style {
+            bracket: Bracket::Square,
+            space_around_comparison: true,
+            space_around_logic: false,
+            space_around_arithmetic: true,
+            space_after_comma: true,
+            remove_redundant_groupings: false,
+        };
+        let source = "((a=1,2)) & {b==1 | c=1:3}";
+        assert_eq!(format(source, &style).unwrap(), "[[a = 1, 2]]&[b = 1|c = 1:3]");
+        let source = "count(a=1,b=1)>=max(c,d+1) & any(q01;a1..a3,b*)=1";
+        assert_eq!(format(source, &style).unwrap(), "count(a = 1, b = 1) >= max(c, d + 1)&any(q01;a1..a3, b*) = 1");
+
+        // Compact style without any spaces
+        let style = Style { space_around_logic: false, space_around_arithmetic: false, ..Style::default() };
+        let cases = vec![
+            ("a = ((b + 1)) * 2", "a=(b+1)*2"),
+            ("a = - (b - 1) / 2 | c = d - -1", "a=-(b-1)/2|c=d--1"),
+            ("a = b - c * 2 | a = b - 1 | a = 2 - b", "a=b - c*2|a=b-1|a=2-b"),
+            ("count(a=1, b = 1:3) >= max(c, d + 1)", "count(a=1,b=1:3)>=max(c,d+1)"),
+            ("any(q01;a1..a3, b*) = 1 & all(c, d) != e * 2", "any(q01;a1..a3,b*)=1&all(c,d)!=e*2"),
+        ];
+        for (source, expected) in cases {
+            let formatted = format(source, &style).unwrap();
+            assert_eq!(formatted, expected, "Input: {}", source);
+            assert!(crate::parse(&formatted).expr.unwrap().structurally_eq(&crate::parse(source).expr.unwrap()));
+        }
+    }
+}
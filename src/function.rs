@@ -0,0 +1,124 @@
+//! Registry of functions that can be called in filters, e.g. "missing(hl0001)" or
+//! "count(hl0010=1, hl0011=1) >= 2". The parser checks calls against the signatures in the
+//! registry, so unknown functions and wrong arguments are reported while parsing.
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// Type of a function argument or result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    /// Arithmetic operand, including items. Ex.: "hl0001 + 1"
+    Number,
+    /// Reference to an item. Ex.: "q01;hl0001"
+    Item,
+    /// Filter expression. Ex.: "hl0001=1 | hl0002=1"
+    Boolean,
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Type::Number => write!(f, "number"),
+            Type::Item => write!(f, "item"),
+            Type::Boolean => write!(f, "filter"),
+        }
+    }
+}
+
+/// Signature of a function. If `variadic` is true, the last parameter can be repeated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub parameters: Vec<Type>,
+    pub variadic: bool,
+    pub result: Type,
+}
+
+impl Function {
+    pub fn new(name: &str, parameters: &[Type], variadic: bool, result: Type) -> Self {
+        Function { name: name.to_string(), parameters: parameters.to_vec(), variadic, result }
+    }
+
+    /// Returns the type of the parameter at `index`, or `None` if the function takes fewer
+    /// arguments.
+    pub fn parameter(&self, index: usize) -> Option<Type> {
+        match self.parameters.get(index) {
+            Some(parameter) => Some(*parameter),
+            None if self.variadic => self.parameters.last().copied(),
+            None => None,
+        }
+    }
+
+    /// Describes the number of expected arguments, e.g. "1 argument" or "at least 2 arguments".
+    pub fn arity(&self) -> String {
+        let count = self.parameters.len();
+        let plural = if count == 1 { "" } else { "s" };
+        if self.variadic {
+            format!("at least {} argument{}", count, plural)
+        } else {
+            format!("{} argument{}", count, plural)
+        }
+    }
+}
+
+/// Functions known to the parser, by name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Registry {
+    functions: HashMap<String, Function>,
+}
+
+impl Registry {
+    /// Creates a registry without any functions.
+    pub fn new() -> Self {
+        Registry { functions: HashMap::new() }
+    }
+
+    /// Creates a registry with the built-in functions:
+    /// - `missing(item)`: true if the item has no valid answer
+    /// - `count(filter, ...)`: number of true filters
+    /// - `abs(number)`: absolute value
+    /// - `min(number, number, ...)`, `max(number, number, ...)`: smallest and largest value
+    pub fn builtins() -> Self {
+        let mut registry = Registry::new();
+        registry.register(Function::new("missing", &[Type::Item], false, Type::Boolean));
+        registry.register(Function::new("count", &[Type::Boolean], true, Type::Number));
+        registry.register(Function::new("abs", &[Type::Number], false, Type::Number));
+        registry.register(Function::new("min", &[Type::Number, Type::Number], true, Type::Number));
+        registry.register(Function::new("max", &[Type::Number, Type::Number], true, Type::Number));
+        registry
+    }
+
+    /// Adds a function, replacing any function with the same name.
+    pub fn register(&mut self, function: Function) {
+        self.functions.insert(function.name.clone(), function);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Function> {
+        self.functions.get(name)
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Registry::builtins()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signatures() {
+        let registry = Registry::builtins();
+        let min = registry.get("min").unwrap();
+        assert_eq!((min.parameter(0), min.parameter(5)), (Some(Type::Number), Some(Type::Number)));
+        assert_eq!(min.arity(), "at least 2 arguments");
+
+        let missing = registry.get("missing").unwrap();
+        assert_eq!((missing.parameter(0), missing.parameter(1)), (Some(Type::Item), None));
+        assert_eq!(missing.arity(), "1 argument");
+        assert!(registry.get("sum").is_none());
+    }
+}
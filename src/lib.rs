@@ -1,6 +1,522 @@
+pub mod catalog;
+pub mod code;
+pub mod cst;
+pub mod document;
 pub mod error;
 pub mod expr;
+pub mod formatter;
+pub mod function;
+pub mod lint;
 pub mod parser;
 pub mod scanner;
+pub mod span;
 pub mod token_type;
 
+use crate::code::Code;
+use crate::document::Document;
+use crate::error::ParsingError;
+use crate::expr::Expr;
+use crate::parser::Parser;
+use crate::scanner::{Scanner, Strictness};
+
+/// Result of parsing a filter expression. Holds the syntax tree, if one could be built, and
+/// every diagnostic reported by the scanner and the parser. Warnings of `lint` are kept apart
+/// from errors, as they do not fail the parse.
+#[derive(Debug, Clone)]
+pub struct ParseResult {
+    pub expr: Option<Expr>,
+    pub errors: Vec<ParsingError>,
+    pub warnings: Vec<ParsingError>,
+}
+
+impl ParseResult {
+    /// Returns true if a syntax tree was built without any diagnostics.
+    pub fn is_ok(&self) -> bool {
+        self.expr.is_some() && self.errors.is_empty()
+    }
+
+    /// Converts the result into the syntax tree or the list of diagnostics.
+    pub fn into_result(self) -> Result<Expr, Vec<ParsingError>> {
+        match self.expr {
+            Some(expr) if self.errors.is_empty() => Ok(expr),
+            _ => Err(self.errors),
+        }
+    }
+}
+
+/// Parses a filter expression such as "q01;elb0001=1 & q02;elb0002=1:3".
+///
+/// Scanning and parsing errors do not abort early: all diagnostics that could be collected are
+/// returned in `ParseResult::errors`. Internal parser errors are not included. Warnings are
+/// returned in `ParseResult::warnings`.
+pub fn parse(source: &str) -> ParseResult {
+    parse_with_strictness(source, Strictness::default())
+}
+
+/// Parses a filter expression like `parse`. With `Strictness::Strict`, alternative spellings of
+/// operators such as "and" or "<>" are reported as errors.
+pub fn parse_with_strictness(source: &str, strictness: Strictness) -> ParseResult {
+    let mut parser = Parser::new(Scanner::with_strictness(source, strictness));
+    let expr = parser.parse().ok();
+    let mut warnings = parser.warnings();
+    if let Some(expr) = &expr {
+        warnings.extend(lint::lint_expr(source, expr));
+        lint::sort(&mut warnings);
+    }
+
+    let errors = parser.into_errors()
+        .into_iter()
+        .filter(|error| matches!(error, ParsingError::Report { .. }))
+        .collect();
+
+    // In strict mode, alternative spellings of operators are errors already
+    if strictness == Strictness::Strict {
+        warnings.retain(|warning| warning.code() != Some(Code::AlternativeOperator));
+    }
+
+    ParseResult { expr, errors, warnings }
+}
+
+/// Parses a document of named filters, one filter per line, such as "adults: hl0001 >= 18".
+/// Comments and filters spanning several lines are allowed, see `document`.
+pub fn parse_document(source: &str) -> Document {
+    Document::parse(source)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+
+    use super::*;
+    use crate::expr::{Bound, Comparison, Element, Ident, Logic, Operand};
+    use crate::scanner::Numeric;
+    use crate::span::Span;
+
+    #[test]
+    fn test_parse_result() {
+        let result = parse("q01;hl0001=1 & q02;hl0012=3");
+        assert!(result.is_ok());
+        assert!(result.into_result().is_ok());
+
+        let cases = vec![
+            ("q01;elb0001=", Code::ExpectedValue, "Expected number, string, list, range, or item", 1, 13),
+            ("q01;hl0001=1  q02;hl0012=3", Code::MissingLogicalOperator, "Expected '&' or '|'", 1, 15),
+            ("", Code::ExpectedFilter, "Expected filter expression", 1, 1),
+        ];
+
+        for (source, expected_code, expected, expected_line, expected_column) in cases {
+            let result = parse(source);
+            assert!(result.expr.is_none(), "Expected parse to fail. Input: {}", source);
+            match result.errors.first() {
+                Some(ParsingError::Report { code, message, line, column, .. }) => {
+                    assert_eq!(*code, expected_code, "Input: {}", source);
+                    assert_eq!((message.as_str(), *line, *column), (expected, expected_line, expected_column), "Input: {}", source);
+                },
+                other => panic!("Expected reported error for {:?}, got {:?}", source, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_errors_after_early_return() {
+        let result = parse("q01;hl0001=1 q02;hl0012=3 @");
+        let codes: Vec<Option<Code>> = result.errors.iter().map(ParsingError::code).collect();
+        assert_eq!(codes, vec![Some(Code::MissingLogicalOperator), Some(Code::UnexpectedCharacter)]);
+
+        let document = Document::parse("a: hl0001=1 hl0002=1 @\nb: hl0003=1");
+        let codes: Vec<Option<Code>> = document.errors.iter().map(ParsingError::code).collect();
+        assert_eq!(codes, vec![Some(Code::MissingLogicalOperator), Some(Code::UnexpectedCharacter)]);
+
+        // Tokens after the error are linted as well
+        let result = parse("hl0001=1 hl0002=1 and hl0003 <> 1");
+        let codes: Vec<Option<Code>> = result.warnings.iter().map(ParsingError::code).collect();
+        assert_eq!(codes, vec![Some(Code::AlternativeOperator); 2]);
+    }
+
+    #[test]
+    fn test_error_spans() {
+        let cases = vec![
+            ("hl0001 = 1 & (hl0002 = 1 | hl0003 = 1", "", vec!["("]),
+            ("hl0001 = (hl0002 + 1 * 2", "", vec!["("]),
+            ("hl0001 = 1 & hl0002 = 1.2.3", ".", vec![]),
+            ("1 + 2 = 3", "1 + 2", vec![]),
+            ("missing(hl0001) = 1", "missing(hl0001)", vec![]),
+            ("count(hl0001=1 hl0002=1)", "hl0002", vec!["("]),
+            ("any(hl0010..hz0001) = 1", "hz0001", vec!["hl0010"]),
+            ("hl0001 = 1 & \"text\"", "\"text\"", vec![]),
+        ];
+        for (source, expected, expected_labels) in cases {
+            let result = parse(source);
+            let Some(ParsingError::Report { span, labels, .. }) = result.errors.first() else {
+                panic!("Expected reported error for {:?}, got {:?}", source, result.errors)
+            };
+            assert_eq!(&source[Range::from(*span)], expected, "Input: {}", source);
+            let labels: Vec<&str> = labels.iter().map(|label| &source[Range::from(label.span)]).collect();
+            assert_eq!(labels, expected_labels, "Input: {}", source);
+        }
+    }
+
+    #[test]
+    fn test_suggestions() {
+        let cases = vec![
+            ("q01:elb001 = 2", Code::ExpectedComparison, Some("Use ';' to separate question and item: 'q01;elb001'")),
+            ("q01.elb001 = 2", Code::MisplacedDot, Some("Use ';' to separate question and item: 'q01;elb001'")),
+            ("q01-elb001 = 2", Code::AmbiguousMinus, Some("Write 'q01;elb001' for item 'elb001' of question 'q01', or 'q01 - elb001' to subtract")),
+            ("q01;elb001 = 2_3", Code::MissingLogicalOperator, Some("Use ':' for a range, '2:3', or ',' for a list, '2,3'")),
+            ("q01;hl0001=1  q02;hl0012=3", Code::MissingLogicalOperator, Some("Insert '&' or '|' before 'q02'")),
+            ("hl0001 = 1.2.3", Code::MisplacedDot, None),
+            ("q01;hl0001:elb001 = 2", Code::ExpectedComparison, None),
+        ];
+        for (source, expected_code, expected) in cases {
+            let result = parse(source);
+            let error = result.errors.first().unwrap_or_else(|| panic!("Expected error. Input: {}", source));
+            assert_eq!(error.code(), Some(expected_code), "Input: {}", source);
+            assert_eq!(error.help(), expected, "Input: {}", source);
+        }
+    }
+
+    #[test]
+    fn test_spans() {
+        let source = "q01;hl0001=1 & [q02;hl0012=3 | q03;hl041=4]";
+        let expr = parse(source).expr.unwrap();
+        assert_eq!(expr.span(), Span::new(0, source.len()));
+
+        let Expr::Logical { left, right, .. } = &expr else { panic!("Expected logical expression") };
+        assert_eq!(&source[Range::from(left.span())], "q01;hl0001=1");
+        assert_eq!(&source[Range::from(right.span())], "[q02;hl0012=3 | q03;hl041=4]");
+
+        let Expr::Grouping { expr: inner, .. } = right.as_ref() else { panic!("Expected grouping") };
+        assert_eq!(&source[Range::from(inner.span())], "q02;hl0012=3 | q03;hl041=4");
+
+        let source = "hl0001 = -1, 2,3";
+        let Some(Expr::Filter { left, right, .. }) = parse(source).expr else { panic!("Expected filter") };
+        assert_eq!(&source[Range::from(left.span())], "hl0001");
+        assert_eq!(&source[Range::from(right.span())], "-1, 2,3");
+    }
+
+    #[test]
+    fn test_numbers() {
+        let cases = vec![
+            ("hl0001 = 1", Numeric::Integer(1)),
+            ("hl0001 = -12", Numeric::Integer(-12)),
+            ("hl0001 = 1.324", Numeric::Decimal(1.324)),
+            ("hl0001 = -0.5", Numeric::Decimal(-0.5)),
+        ];
+        for (source, expected) in cases {
+            let Some(Expr::Filter { right, .. }) = parse(source).expr else { panic!("Expected filter") };
+            let Operand::Number { value, .. } = right else { panic!("Expected number in {}", source) };
+            assert_eq!(value, expected, "Input: {}", source);
+        }
+
+        let Some(Expr::Filter { right, .. }) = parse("hl0001 = -2,-1,1,2.5").expr else { panic!("Expected filter") };
+        let Operand::List { elements, .. } = right else { panic!("Expected list") };
+        let numbers: Vec<Numeric> = elements.iter()
+            .map(|element| match element { Element::Value(value) => value.number, _ => panic!("Expected value") })
+            .collect();
+        assert_eq!(numbers, vec![Numeric::Integer(-2), Numeric::Integer(-1), Numeric::Integer(1), Numeric::Decimal(2.5)]);
+
+        assert!(!parse("hl0001 = 99999999999999999999").is_ok());
+
+        // The smallest integer can not be negated
+        let Some(Expr::Filter { right, .. }) = parse("hl0001 = - -9223372036854775807").expr else { panic!("Expected filter") };
+        assert!(matches!(right, Operand::Number { value: Numeric::Integer(i64::MAX), .. }));
+        let result = parse("hl0001 = --9223372036854775808");
+        assert_eq!(result.errors[0].code(), Some(Code::InvalidNumber));
+        assert_eq!(result.errors[0].span(), Some(Span::new(9, 30)));
+    }
+
+    #[test]
+    fn test_lists() {
+        let source = "hl0001 = -2,-1,3:5,8";
+        let Some(Expr::Filter { right: Operand::List { elements, span }, .. }) = parse(source).expr else {
+            panic!("Expected list")
+        };
+        assert_eq!(&source[Range::from(span)], "-2,-1,3:5,8");
+        let texts: Vec<&str> = elements.iter().map(|element| &source[Range::from(element.span())]).collect();
+        assert_eq!(texts, vec!["-2", "-1", "3:5", "8"]);
+        let Element::Range { start, end, .. } = &elements[2] else { panic!("Expected range") };
+        assert_eq!((start.value().unwrap().number, end.value().unwrap().number), (Numeric::Integer(3), Numeric::Integer(5)));
+
+        // A single range is not a list
+        let Some(Expr::Filter { right, .. }) = parse("hl0001 = 1:3").expr else { panic!("Expected filter") };
+        assert!(matches!(right, Operand::Range { .. }));
+        let Some(Expr::Filter { right, .. }) = parse("hl0001 = 1:3 , 7:9").expr else { panic!("Expected filter") };
+        assert!(matches!(right, Operand::List { elements, .. } if elements.len() == 2));
+
+        // Open ranges
+        let source = "hl0001 = :-1, 3 , 8:";
+        let Some(Expr::Filter { right: Operand::List { elements, .. }, .. }) = parse(source).expr else {
+            panic!("Expected list")
+        };
+        let texts: Vec<&str> = elements.iter().map(|element| &source[Range::from(element.span())]).collect();
+        assert_eq!(texts, vec![":-1", "3", "8:"]);
+        assert!(matches!(&elements[0], Element::Range { start: Bound::Unbounded, end: Bound::Included(_), .. }));
+        assert!(matches!(&elements[2], Element::Range { start: Bound::Included(_), end: Bound::Unbounded, .. }));
+        let Some(Expr::Filter { right, .. }) = parse("hl0001 <= 3:").expr else { panic!("Expected filter") };
+        assert!(matches!(right, Operand::Range { end: Bound::Unbounded, .. }));
+    }
+
+    #[test]
+    fn test_typed_tree() {
+        let cases = vec![
+            ("hl0001 = 1", Comparison::Equal),
+            ("hl0001 == 1", Comparison::Equal),
+            ("hl0001 != 1", Comparison::NotEqual),
+            ("hl0001 > 1", Comparison::Greater),
+            ("hl0001 >= 1", Comparison::GreaterEqual),
+            ("hl0001 < 1", Comparison::Less),
+            ("hl0001 <= 1", Comparison::LessEqual),
+        ];
+        for (source, expected) in cases {
+            let Some(Expr::Filter { operator, .. }) = parse(source).expr else { panic!("Expected filter") };
+            assert_eq!(operator, expected, "Input: {}", source);
+        }
+
+        let Some(Expr::Logical { left, operator, right, .. }) = parse("q01;hl0001=1:3 | q02;hl0012=hl0001").expr else {
+            panic!("Expected logical expression")
+        };
+        assert_eq!(operator, Logic::Or);
+
+        let Expr::Filter { left: item, right: range, .. } = *left else { panic!("Expected filter") };
+        let Operand::Item { question: Some(Ident { name: question, .. }), item: Ident { name: item, .. }, .. } = item else {
+            panic!("Expected qualified item")
+        };
+        assert_eq!((question.as_str(), item.as_str()), ("q01", "hl0001"));
+        let Operand::Range { start, end, .. } = range else { panic!("Expected range") };
+        assert_eq!((start.value().unwrap().number, end.value().unwrap().number), (Numeric::Integer(1), Numeric::Integer(3)));
+
+        let Expr::Filter { right, .. } = *right else { panic!("Expected filter") };
+        assert!(matches!(right, Operand::Item { question: None, .. }));
+    }
+
+    #[test]
+    fn test_print_round_trip() {
+        let cases = vec![
+            ("q01;elb0001=1", "q01;elb0001=1"),
+            ("01;elb0001 == 1.324", "01;elb0001=1.324"),
+            ("-1a;elb0001>=2.0", "-1a;elb0001>=2.0"),
+            ("q01;elb001 = -2:-3", "q01;elb001=-2:-3"),
+            ("q01;hl0001 = -1, -2,4", "q01;hl0001=-1,-2,4"),
+            ("q02;hl0012 != q01;hl001", "q02;hl0012!=q01;hl001"),
+            ("q01;hl0001=1&q02;hl0012=3 & q03;hl041=4", "q01;hl0001=1 & q02;hl0012=3 & q03;hl041=4"),
+            ("q01;hl0001=1 & q02;hl0012=3 | q03;hl041=4", "q01;hl0001=1 & q02;hl0012=3 | q03;hl041=4"),
+            ("q01;hl0001=1 & [q02;hl0012=3 | q03;hl041=4]", "q01;hl0001=1 & (q02;hl0012=3 | q03;hl041=4)"),
+            ("{(q01;hl0001=1)}", "((q01;hl0001=1))"),
+        ];
+        for (source, expected) in cases {
+            let expr = parse(source).expr.unwrap();
+            let printed = expr.to_string();
+            assert_eq!(printed, expected);
+            let reparsed = parse(&printed).expr.unwrap();
+            assert!(reparsed.structurally_eq(&expr), "Round trip failed for {}", source);
+        }
+
+        // Trees built without groupings get brackets where precedence requires them
+        let filter = |item: &str| Expr::Filter {
+            left: Operand::Item { question: None, item: Ident { name: item.to_string(), span: Span::default() }, span: Span::default() },
+            operator: Comparison::Equal,
+            right: Operand::Number { value: Numeric::Integer(1), span: Span::default() },
+            span: Span::default(),
+        };
+        let logical = |left: Expr, operator: Logic, right: Expr| Expr::Logical {
+            left: Box::new(left), operator, right: Box::new(right), span: Span::default()
+        };
+        let expr = logical(
+            logical(filter("a"), Logic::Or, filter("b")),
+            Logic::And,
+            logical(filter("c"), Logic::And, filter("d")),
+        );
+        assert_eq!(expr.to_string(), "(a=1 | b=1) & (c=1 & d=1)");
+        assert!(parse(&expr.to_string()).expr.unwrap().structurally_eq(&expr));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json() {
+        let expr = parse("q01;hl0001=1.5 & hl0002!=-1,2").expr.unwrap();
+        let json = serde_json::to_value(&expr).unwrap();
+        let expected = serde_json::json!({
+            "type": "logical",
+            "operator": "&",
+            "left": {
+                "type": "filter",
+                "left": {
+                    "type": "item",
+                    "question": { "name": "q01", "span": { "start": 0, "end": 3 } },
+                    "item": { "name": "hl0001", "span": { "start": 4, "end": 10 } },
+                    "span": { "start": 0, "end": 10 },
+                },
+                "operator": "=",
+                "right": { "type": "number", "value": 1.5, "span": { "start": 11, "end": 14 } },
+                "span": { "start": 0, "end": 14 },
+            },
+            "right": {
+                "type": "filter",
+                "left": {
+                    "type": "item",
+                    "question": null,
+                    "item": { "name": "hl0002", "span": { "start": 17, "end": 23 } },
+                    "span": { "start": 17, "end": 23 },
+                },
+                "operator": "!=",
+                "right": {
+                    "type": "list",
+                    "elements": [
+                        { "type": "value", "number": -1, "span": { "start": 25, "end": 27 } },
+                        { "type": "value", "number": 2, "span": { "start": 28, "end": 29 } },
+                    ],
+                    "span": { "start": 25, "end": 29 },
+                },
+                "span": { "start": 17, "end": 29 },
+            },
+            "span": { "start": 0, "end": 29 },
+        });
+        assert_eq!(json, expected);
+
+        let deserialized: Expr = serde_json::from_value(json).unwrap();
+        assert_eq!(deserialized, expr);
+    }
+
+    #[test]
+    fn test_calls() {
+        use crate::expr::Argument;
+        use crate::function::{Function, Registry, Type};
+        use crate::parser::Parser;
+
+        let source = "count(hl0010=1, hl0011=1) >= 2 & !missing(hl0001)";
+        let Some(Expr::Logical { left, right, .. }) = parse(source).expr else { panic!("Expected '&'") };
+        let Expr::Filter { left: Operand::Call { name, arguments, span }, .. } = *left else { panic!("Expected call") };
+        assert_eq!((name.name.as_str(), &source[Range::from(span)]), ("count", "count(hl0010=1, hl0011=1)"));
+        assert!(arguments.iter().all(|argument| matches!(argument, Argument::Filter(Expr::Filter { .. }))));
+        assert_eq!(&source[Range::from(arguments[1].span())], "hl0011=1");
+        let Expr::Not { expr, .. } = *right else { panic!("Expected negation") };
+        assert!(matches!(*expr, Expr::Call { arguments, .. } if matches!(arguments[..], [Argument::Operand(Operand::Item { .. })])));
+
+        // A question number after a comma starts the next argument, not a list element
+        for (source, expected) in [("count(a=1, 02;b=1) >= 1", 2), ("count(a=1,2, -1;b=1, 3;c=2,3) >= 1", 3)] {
+            let Some(Expr::Filter { left: Operand::Call { arguments, .. }, .. }) = parse(source).expr else {
+                panic!("Expected call. Input: {}", source)
+            };
+            assert_eq!(arguments.len(), expected, "Input: {}", source);
+        }
+
+        // Custom registry
+        let mut functions = Registry::new();
+        functions.register(Function::new("valid", &[Type::Item], false, Type::Boolean));
+        let mut parser = Parser::with_functions(Scanner::new("valid(hl0001) & hl0002 = 1"), functions.clone());
+        assert!(parser.parse().is_ok());
+        let mut parser = Parser::with_functions(Scanner::new("missing(hl0001)"), functions);
+        assert!(parser.parse().is_err());
+        assert!(parser.errors().iter().any(|error| error.to_string() == "Unknown function 'missing' (1:1)"));
+    }
+
+    #[test]
+    fn test_values() {
+        use crate::expr::{Fold, Value, Visitor};
+
+        struct Values(usize);
+        impl Visitor for Values {
+            fn visit_value(&mut self, _value: &Value) {
+                self.0 += 1;
+            }
+        }
+        let expr = parse("a=1 & b=1,2 & c=-3:4").expr.unwrap();
+        let mut values = Values(0);
+        values.visit_expr(&expr);
+        assert_eq!(values.0, 5);
+
+        struct Double;
+        impl Fold for Double {
+            fn fold_value(&mut self, value: Value) -> Value {
+                let number = match value.number {
+                    Numeric::Integer(number) => Numeric::Integer(number * 2),
+                    Numeric::Decimal(number) => Numeric::Decimal(number * 2.0),
+                };
+                Value { number, ..value }
+            }
+        }
+        assert_eq!(Double.fold_expr(expr).to_string(), "a=2 & b=2,4 & c=-6:8");
+
+        // Lists do not mix strings with numbers or ranges
+        let cases = vec![
+            ("a = 'x', 1", "Expected string like the first element of the list (1:10)"),
+            ("a = 1, 2:3, \"x\"", "Expected number or range like the first element of the list (1:13)"),
+            ("a = :3, 'x'", "Expected number or range like the first element of the list (1:9)"),
+        ];
+        for (source, expected) in cases {
+            let result = parse(source);
+            assert_eq!(result.errors[0].code(), Some(Code::MixedListTypes), "Input: {}", source);
+            assert_eq!(result.errors[0].to_string(), expected, "Input: {}", source);
+        }
+        assert!(parse("a = 'x', 'y' & b = 1, 2:3").is_ok());
+    }
+
+    #[test]
+    fn test_quantifiers() {
+        use crate::expr::{Quantifier, Selector};
+
+        let source = "all(q01;hl0010..hl0025, hl01*) != -1";
+        let Some(Expr::Quantified { quantifier: Quantifier::All, items, operator: Comparison::NotEqual, right, span }) = parse(source).expr else {
+            panic!("Expected quantifier")
+        };
+        assert_eq!(span, Span::new(0, source.len()));
+        assert!(matches!(right, Operand::Number { value: Numeric::Integer(-1), .. }));
+        let Selector::Range { question: Some(question), start, end, span } = &items[0] else { panic!("Expected item range") };
+        assert_eq!((question.name.as_str(), start.name.as_str(), end.name.as_str()), ("q01", "hl0010", "hl0025"));
+        assert_eq!(&source[Range::from(*span)], "q01;hl0010..hl0025");
+        assert!(matches!(&items[1], Selector::Pattern { question: None, pattern, .. } if pattern.name == "hl01*"));
+
+        // Wide ranges are rejected without building their items
+        let result = parse("any(a000000000..a999999999) = 1");
+        assert_eq!(result.errors.first().and_then(ParsingError::code), Some(Code::InvalidItemRange));
+        assert!(parse("any(hl0000..hl9999) = 1").is_ok());
+    }
+
+    #[test]
+    fn test_negation() {
+        let source = "!q01;hl0001=1 & q02;hl0012=2 | !(q03;hl0013=3)";
+        let Some(Expr::Logical { left, operator: Logic::Or, right, .. }) = parse(source).expr else {
+            panic!("Expected '|' at top level")
+        };
+        let Expr::Logical { left: negated, operator: Logic::And, .. } = *left else { panic!("Expected '&'") };
+        let Expr::Not { expr, span } = *negated else { panic!("Expected negation") };
+        assert!(matches!(*expr, Expr::Filter { .. }));
+        assert_eq!(&source[Range::from(span)], "!q01;hl0001=1");
+
+        let Expr::Not { expr, span } = *right else { panic!("Expected negation") };
+        assert!(matches!(*expr, Expr::Grouping { .. }));
+        assert_eq!(&source[Range::from(span)], "!(q03;hl0013=3)");
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        use crate::expr::Arithmetic;
+
+        // '*' binds stronger than '-', both are left-associative
+        let source = "hl0001 > hl0002 - hl0003 * 2 - -1";
+        let Some(Expr::Filter { right, .. }) = parse(source).expr else { panic!("Expected filter") };
+        let Operand::Binary { left, operator: Arithmetic::Subtract, right: last, span } = right else {
+            panic!("Expected '-' at top level")
+        };
+        assert_eq!(&source[Range::from(span)], "hl0002 - hl0003 * 2 - -1");
+        assert!(matches!(*last, Operand::Number { value: Numeric::Integer(-1), .. }));
+        let Operand::Binary { right: product, operator: Arithmetic::Subtract, .. } = *left else { panic!("Expected '-'") };
+        assert!(matches!(*product, Operand::Binary { operator: Arithmetic::Multiply, .. }));
+
+        let source = "hl0001 = -(hl0002 / 2)";
+        let Some(Expr::Filter { right: Operand::Negative { operand, span }, .. }) = parse(source).expr else {
+            panic!("Expected negative operand")
+        };
+        assert_eq!(&source[Range::from(span)], "-(hl0002 / 2)");
+        assert_eq!(&source[Range::from(operand.span())], "(hl0002 / 2)");
+
+        // Parentheses on the left-hand side belong to the operand, not to a grouping of filters
+        let source = "(q05;pl0001 - q05;pl0002) >= 18";
+        let Some(Expr::Filter { left: Operand::Grouping { operand, span }, operator: Comparison::GreaterEqual, .. }) = parse(source).expr else {
+            panic!("Expected filter with operand in parentheses")
+        };
+        assert_eq!(&source[Range::from(span)], "(q05;pl0001 - q05;pl0002)");
+        assert!(matches!(*operand, Operand::Binary { operator: Arithmetic::Subtract, .. }));
+    }
+}
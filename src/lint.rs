@@ -0,0 +1,257 @@
+//! Lints for filters that are valid, but suspicious or not written in the canonical filter
+//! syntax. Lints are reported as `ParsingError::Warning`.
+
+use crate::catalog::Catalog;
+use crate::code::Code;
+use crate::error::ParsingError;
+use crate::expr::{walk_expr, walk_operand, Expr, Ident, Logic, Operand, Selector, Visitor};
+use crate::scanner::{Scanner, Token};
+use crate::span::Span;
+use crate::token_type::TokenType::*;
+
+/// Returns the warnings of `source` in order of their position. If the filter was parsed,
+/// `expr` is its syntax tree. Tokens that can not be scanned are skipped, they are reported by
+/// the parser. Scans `source` again, `Parser::warnings` has the warnings of the parsed tokens.
+pub fn lint(source: &str, expr: Option<&Expr>) -> Vec<ParsingError> {
+    let mut linter = TokenLinter::default();
+    Scanner::new(source).filter_map(Result::ok).for_each(|token| linter.lint(&token));
+    let mut warnings = linter.warnings();
+    if let Some(expr) = expr {
+        warnings.extend(lint_expr(source, expr));
+    }
+    sort(&mut warnings);
+    warnings
+}
+
+/// Lints of the tokens of a filter, one token at a time as the parser takes them:
+/// - alternative spellings of operators, ex.: "and" or "<>"
+/// - '==' mixed with '='
+#[derive(Debug, Default)]
+pub(crate) struct TokenLinter {
+    warnings: Vec<ParsingError>,
+    double_equals: Vec<ParsingError>, // reported only if '=' is used as well
+    mixed_equal: bool,
+}
+
+impl TokenLinter {
+    pub(crate) fn lint(&mut self, token: &Token) {
+        if token.is_alternative() {
+            let canonical = token.variant.canonical().unwrap_or_default();
+            let message = format!("Use '{}' instead of '{}'", canonical, token.lexeme);
+            self.warnings.push(warning(Code::AlternativeOperator, message, token.line, token.column, token.span));
+        } else if token.variant == EqualEqual {
+            let message = "'==' mixed with '=', use '='".to_string();
+            self.double_equals.push(warning(Code::DoubleEqual, message, token.line, token.column, token.span));
+        } else if token.variant == Equal {
+            self.mixed_equal = true;
+        }
+    }
+
+    /// Returns the warnings of the tokens linted so far, in order of their position.
+    pub(crate) fn warnings(&self) -> Vec<ParsingError> {
+        let mut warnings = self.warnings.clone();
+        if self.mixed_equal {
+            warnings.extend(self.double_equals.iter().cloned());
+        }
+        sort(&mut warnings);
+        warnings
+    }
+}
+
+/// Lints of the syntax tree of a filter:
+/// - redundant brackets, ex.: "((hl0001=1))"
+/// - '&' within '|' without brackets, ex.: "hl0001=1 | hl0002=1 & hl0003=1"
+/// - lists with one element, which the parser does not create, but a `Fold` or JSON input may.
+///   `parse` never reports them.
+pub(crate) fn lint_expr(source: &str, expr: &Expr) -> Vec<ParsingError> {
+    let mut linter = Linter { source, warnings: Vec::new() };
+    linter.visit_expr(expr);
+    linter.warnings
+}
+
+/// Returns the items of `expr` that are not in `catalog`, e.g. the SOEP metadatabase, with the
+/// closest known item as suggestion. Items of ranges are checked at both ends, patterns are
+/// checked by `Catalog::expand`.
+pub fn lint_items(source: &str, expr: &Expr, catalog: &Catalog) -> Vec<ParsingError> {
+    let mut items = Items { source, catalog, warnings: Vec::new() };
+    items.visit_expr(expr);
+    items.warnings
+}
+
+/// Sorts warnings by position.
+pub fn sort(warnings: &mut [ParsingError]) {
+    warnings.sort_by_key(|warning| match warning {
+        ParsingError::Report { line, column, .. }
+        | ParsingError::Internal { line, column, .. }
+        | ParsingError::Warning { line, column, .. } => (*line, *column),
+    });
+}
+
+fn warning(code: Code, message: String, line: usize, column: usize, span: Span) -> ParsingError {
+    ParsingError::Warning { code, message, line, column, span, help: None }
+}
+
+/// Returns the line and column of a byte offset into `source`, both starting at 1.
+pub(crate) fn position(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+    (line, column)
+}
+
+struct Linter<'a> {
+    source: &'a str,
+    warnings: Vec<ParsingError>,
+}
+
+impl Linter<'_> {
+    fn warn(&mut self, code: Code, message: &str, span: Span) {
+        let (line, column) = position(self.source, span.start);
+        self.warnings.push(warning(code, message.to_string(), line, column, span));
+    }
+}
+
+impl Visitor for Linter<'_> {
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Grouping { expr: inner, span } if matches!(inner.as_ref(), Expr::Grouping { .. }) => {
+                self.warn(Code::RedundantGrouping, "Redundant brackets", *span);
+            },
+            Expr::Logical { left, operator: Logic::Or, right, .. } => {
+                for child in [left, right] {
+                    if let Expr::Logical { operator: Logic::And, span, .. } = child.as_ref() {
+                        self.warn(Code::MixedLogic, "'&' binds stronger than '|', add brackets around '&'", *span);
+                    }
+                }
+            },
+            _ => {},
+        }
+        walk_expr(self, expr);
+    }
+
+    fn visit_operand(&mut self, operand: &Operand) {
+        match operand {
+            Operand::Grouping { operand: inner, span } if matches!(inner.as_ref(), Operand::Grouping { .. }) => {
+                self.warn(Code::RedundantGrouping, "Redundant brackets", *span);
+            },
+            Operand::List { elements, span } if elements.len() == 1 => {
+                self.warn(Code::SingleElementList, "List with one element, compare with the element", *span);
+            },
+            _ => {},
+        }
+        walk_operand(self, operand);
+    }
+}
+
+struct Items<'a> {
+    source: &'a str,
+    catalog: &'a Catalog,
+    warnings: Vec<ParsingError>,
+}
+
+impl Items<'_> {
+    fn check(&mut self, item: &Ident) {
+        if self.catalog.contains(&item.name) {
+            return
+        }
+        let (line, column) = position(self.source, item.span.start);
+        let mut warning = warning(Code::UnknownItem, format!("Unknown item '{}'", item.name), line, column, item.span);
+        if let Some(known) = self.catalog.suggest(&item.name) {
+            warning = warning.with_help(format!("Did you mean '{}'?", known));
+        }
+        self.warnings.push(warning);
+    }
+}
+
+impl Visitor for Items<'_> {
+    fn visit_expr(&mut self, expr: &Expr) {
+        if let Expr::Quantified { items, .. } = expr {
+            for selector in items {
+                match selector {
+                    Selector::Item { item, .. } => self.check(item),
+                    Selector::Range { start, end, .. } => {
+                        self.check(start);
+                        self.check(end);
+                    },
+                    Selector::Pattern { .. } => {},
+                }
+            }
+        }
+        walk_expr(self, expr);
+    }
+
+    fn visit_operand(&mut self, operand: &Operand) {
+        if let Operand::Item { item, .. } = operand {
+            self.check(item);
+        }
+        walk_operand(self, operand);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::{Element, Value};
+
+    #[test]
+    fn test_lint() {
+        let cases = vec![
+            ("q01;hl0001=1 & hl0002!=2 & (hl0004=1 | hl0005=1)", vec![]),
+            ("hl0001==1 & hl0002==2", vec![]),
+            ("hl0001=1 and not hl0002~=2", vec!["Use '&' instead of 'and' (1:10)", "Use '!' instead of 'not' (1:14)", "Use '!=' instead of '~=' (1:24)"]),
+            ("hl0001 <> 1 OR\nhl0002 => 2", vec!["Use '!=' instead of '<>' (1:8)", "Use '|' instead of 'OR' (1:13)", "Use '>=' instead of '=>' (2:8)"]),
+            ("hl0001==1 & hl0002=2", vec!["'==' mixed with '=', use '=' (1:7)"]),
+            ("((hl0001=1)) & [(hl0002=1 | hl0003=1)]", vec!["Redundant brackets (1:1)", "Redundant brackets (1:16)"]),
+            ("hl0001 = ((hl0002 + 1)) * 2", vec!["Redundant brackets (1:10)"]),
+            ("hl0001=1 |\n  hl0002=1 & hl0003=1", vec!["'&' binds stronger than '|', add brackets around '&' (2:3)"]),
+        ];
+        for (source, expected) in cases {
+            let expr = crate::parse(source).expr.unwrap();
+            let warnings: Vec<String> = lint(source, Some(&expr)).iter().map(ParsingError::to_string).collect();
+            assert_eq!(warnings, expected, "Input: {}", source);
+        }
+    }
+
+    #[test]
+    fn test_lint_items() {
+        let catalog = Catalog::from_lines("hl0001\nhl0002\nhl0010\npl0001");
+        let cases = vec![
+            ("q01;hl0001=1 & pl0001 = hl0002 + 1", vec![]),
+            ("hl001=1 & q01;pl0002=1", vec!["Unknown item 'hl001' (1:1)", "Unknown item 'pl0002' (1:15)"]),
+            ("all(hl0001..hl0011, pl*) = 1", vec!["Unknown item 'hl0011' (1:13)"]),
+            ("count(xy9999=1) > 0", vec!["Unknown item 'xy9999' (1:7)"]),
+        ];
+        for (source, expected) in cases {
+            let expr = crate::parse(source).expr.unwrap();
+            let warnings: Vec<String> = lint_items(source, &expr, &catalog).iter().map(ParsingError::to_string).collect();
+            assert_eq!(warnings, expected, "Input: {}", source);
+        }
+
+        let expr = crate::parse("hl001=1 & xy9999=1").expr.unwrap();
+        let warnings = lint_items("hl001=1 & xy9999=1", &expr, &catalog);
+        let helps: Vec<Option<&str>> = warnings.iter().map(ParsingError::help).collect();
+        assert_eq!(helps, vec![Some("Did you mean 'hl0001'?"), None]);
+    }
+
+    #[test]
+    fn test_single_element_list() {
+        let source = "hl0001 = 1";
+        let Some(Expr::Filter { left, operator, right: Operand::Number { value, span }, span: filter }) = crate::parse(source).expr else {
+            panic!("Expected filter")
+        };
+        let right = Operand::List { elements: vec![Element::Value(Value { number: value, span })], span };
+        let expr = Expr::Filter { left, operator, right, span: filter };
+
+        let warnings: Vec<String> = lint(source, Some(&expr)).iter().map(ParsingError::to_string).collect();
+        assert_eq!(warnings, vec!["List with one element, compare with the element (1:10)"]);
+
+        // Filters can not contain lists with one element, a trailing comma is an error
+        for source in ["hl0001 = 1", "hl0001 = 1:3", "hl0001 = 'DE'", "hl0001 = 1, 2", "count(hl0001 = 1, hl0002 = 2) = 1"] {
+            let result = crate::parse(source);
+            assert!(result.is_ok() && result.warnings.is_empty(), "Input: {}", source);
+        }
+        let result = crate::parse("hl0001 = 1,");
+        assert_eq!(result.errors[0].code(), Some(Code::InvalidListElement));
+        assert!(result.warnings.is_empty());
+    }
+}
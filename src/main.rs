@@ -1,36 +1,203 @@
 use std::env;
+use std::fs;
+use std::io::{self, BufRead};
+use std::process;
+use std::str::FromStr;
 
+use filter_parser::catalog::Catalog;
+use filter_parser::code::Code;
 use filter_parser::expr::Expr;
 use filter_parser::error::{self, ParsingError};
-use filter_parser::parser::Parser;
-use filter_parser::scanner::{Scanner, Token};
+use filter_parser::formatter::{self, Bracket, Style};
+use filter_parser::lint;
+use filter_parser::scanner::Strictness;
 
+const USAGE: &str = "Usage: filter_parser [--emit debug|filter|json] [--strict] [--deny-warnings] [--items FILE] \"<filter expression>\"
+       filter_parser fmt [--check] [--bracket round|square|curly] [--space-comparison]
+                         [--no-space-logic] [--no-space-arithmetic] [--space-comma] [--keep-groupings]
+                         [\"<filter expression>\"...]
+       filter_parser --explain <code>
+
+With --strict, alternative spellings of operators such as 'and' or '<>' are errors.
+Otherwise, a warning names the canonical operator. With --deny-warnings, warnings fail the
+filter like errors. With --items, items that are not listed in FILE, one item name per line,
+are reported with the closest known item.
+
+fmt formats the given filters, or one filter per line from stdin. With --check, filters are
+not formatted but listed if they differ from the formatted output. Filters with comments and
+documents of named filters can not be formatted.
+
+--explain describes an error code, ex.: F0008, with examples.";
+
+/// Output formats of the syntax tree.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Emit {
+    Debug,  // unformatted syntax tree
+    Filter, // canonical filter text
+    Json,   // syntax tree as JSON, requires the `serde` feature
+}
+
+/// Settings of parsing a filter.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    strictness: Strictness,
+    deny_warnings: bool, // warnings fail the filter
+    catalog: Option<Catalog>, // known items, e.g. of the metadatabase
+}
 
 fn main() {
-    let args: Vec<_> = env::args().collect();
-    if args.len() > 1 {
-        let expr = run(&args[1], true);
-        match expr {
+    let mut args = env::args().skip(1).peekable();
+    if args.peek().map(String::as_str) == Some("fmt") {
+        args.next();
+        run_fmt(args);
+    }
+
+    let mut emit = Emit::Debug;
+    let mut options = Options::default();
+    let mut source = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--emit" => {
+                emit = match args.next().as_deref() {
+                    Some("debug") => Emit::Debug,
+                    Some("filter") => Emit::Filter,
+                    Some("json") => Emit::Json,
+                    _ => exit_with_usage(),
+                }
+            },
+            "--strict" => options.strictness = Strictness::Strict,
+            "--deny-warnings" => options.deny_warnings = true,
+            "--explain" => explain(args.next()),
+            "--items" => options.catalog = Some(read_catalog(args.next())),
+            _ if arg.starts_with("--") => exit_with_usage(),
+            _ => source = Some(arg),
+        }
+    }
+
+    if let Some(source) = source {
+        match run(&source, options, true) {
+            Ok(expr) if emit == Emit::Filter => println!("{}", expr),
+            Ok(expr) if emit == Emit::Json => print_json(&expr),
             Ok(expr) => println!("{:?}", expr),
-            Err(_) => {}
+            Err(_) => process::exit(1),
         }
     }
 }
 
-pub fn run(source: &str, print_error: bool) -> Result<Expr, ParsingError> {
-    let mut errors = Vec::<ParsingError>::new();
-    let mut tokens = Vec::<Token>::new();
+/// Runs the `fmt` subcommand. Exits with status 1 if a filter is invalid or, with `--check`,
+/// not formatted.
+fn run_fmt(mut args: impl Iterator<Item = String>) -> ! {
+    let mut style = Style::default();
+    let mut check = false;
+    let mut sources = Vec::new();
 
-    let mut scanner = Scanner::new(source, &mut tokens, &mut errors);
-    if let Err(_) = scanner.scan() {};
-    let had_error = scanner.had_error;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check" => check = true,
+            "--bracket" => {
+                style.bracket = match args.next().as_deref() {
+                    Some("round") => Bracket::Round,
+                    Some("square") => Bracket::Square,
+                    Some("curly") => Bracket::Curly,
+                    _ => exit_with_usage(),
+                }
+            },
+            "--space-comparison" => style.space_around_comparison = true,
+            "--no-space-logic" => style.space_around_logic = false,
+            "--no-space-arithmetic" => style.space_around_arithmetic = false,
+            "--space-comma" => style.space_after_comma = true,
+            "--keep-groupings" => style.remove_redundant_groupings = false,
+            _ if arg.starts_with("--") => exit_with_usage(),
+            _ => sources.push(arg),
+        }
+    }
 
-    let mut parser = Parser::new(&tokens, &mut errors, had_error);
-    let expr = parser.parse();
+    if sources.is_empty() {
+        sources = io::stdin().lock().lines().map_while(Result::ok).filter(|line| !line.trim().is_empty()).collect();
+    }
 
-    if print_error { error::print_errors(source, &errors); }
+    let mut failed = false;
+    for source in sources {
+        match formatter::format(&source, &style) {
+            Ok(formatted) if check => {
+                if formatted != source {
+                    println!("Not formatted: {}", source);
+                    failed = true;
+                }
+            },
+            Ok(formatted) => println!("{}", formatted),
+            Err(errors) => {
+                error::print_errors(&source, &errors);
+                failed = true;
+            },
+        }
+    }
+    process::exit(if failed { 1 } else { 0 })
+}
 
-    expr
+#[cfg(feature = "serde")]
+fn print_json(expr: &Expr) {
+    match serde_json::to_string(expr) {
+        Ok(json) => println!("{}", json),
+        Err(error) => {
+            eprintln!("Failed to serialize syntax tree: {}", error);
+            process::exit(1)
+        },
+    }
+}
+
+#[cfg(not(feature = "serde"))]
+fn print_json(_expr: &Expr) {
+    eprintln!("JSON output requires filter_parser to be built with '--features serde'");
+    process::exit(2)
+}
+
+/// Prints the explanation of an error code.
+fn explain(code: Option<String>) -> ! {
+    let Some(code) = code else { exit_with_usage() };
+    match Code::from_str(&code.to_uppercase()) {
+        Ok(code) => {
+            print!("{}: {}", code, code.explanation());
+            process::exit(0)
+        },
+        Err(_) => {
+            eprintln!("Unknown error code '{}'", code);
+            process::exit(2)
+        },
+    }
+}
+
+/// Reads the item names of `--items FILE`.
+fn read_catalog(path: Option<String>) -> Catalog {
+    let Some(path) = path else { exit_with_usage() };
+    match fs::read_to_string(&path) {
+        Ok(text) => Catalog::from_lines(&text),
+        Err(error) => {
+            eprintln!("Can not read items from '{}': {}", path, error);
+            process::exit(2)
+        },
+    }
+}
+
+fn exit_with_usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2)
+}
+
+pub fn run(source: &str, options: Options, print_error: bool) -> Result<Expr, Vec<ParsingError>> {
+    let mut result = filter_parser::parse_with_strictness(source, options.strictness);
+    if let (Some(catalog), Some(expr)) = (&options.catalog, &result.expr) {
+        result.warnings.extend(lint::lint_items(source, expr, catalog));
+        lint::sort(&mut result.warnings);
+    }
+
+    if print_error { error::print_errors(source, &[result.errors.as_slice(), &result.warnings].concat()); }
+
+    if options.deny_warnings && result.errors.is_empty() && !result.warnings.is_empty() {
+        return Err(result.warnings)
+    }
+    result.into_result()
 }
 
 
@@ -58,6 +225,32 @@ mod tests {
             "q01;hl0001 = 1, 2, 4",
             "q01;hl0001 = 1,2,4",
             "q01;hl0001 = -1,-2,4",
+            "q01;hl0001 = 1,3:5",
+            "q01;hl0001 = 1:3,7:9",
+            "q01;hl0001 = -2,-1,3:5,8",
+            "elb03 = 1:",
+            "elb03 = :5",
+            "elb03 = 1,3:",
+            "elb03 = -2,-1,3:,:-5",
+            "elb03 >= :5",
+            "elb03 <= 5:",
+            "(elb03 = 5:) & elb04 != :0",
+            "pl0001 = 'DE'",
+            "pl0001 != \"DE\" & pl0002 == 'it\\'s'",
+            "pl0001 = 'DE', 'AT', \"CH\"",
+            "missing(hl0001)",
+            "!missing(q01;hl0001) & hl0001 = 1",
+            "count(hl0010=1, hl0011=1, hl0012=1) >= 2",
+            "count(hl0010=1,2, hl0011=1 | hl0012=1) = 1",
+            "abs(hl0001 - hl0002) <= 2",
+            "hl0001 = max(hl0002, hl0003, 0) + min(hl0004, 1)",
+            "(count(hl0010=1, hl0011=1) + 1) * 2 > 2",
+            "any(hl0010..hl0025) = 1",
+            "all(hl00*) != -1",
+            "all(q01;hl0010..hl0015, hl0020, q02;hl01* ) >= 1:3 | !any(pl0001, pl0002) = 'DE'",
+            "any(hl0001..hl0001) = hl0002 * 2",
+            "q01;hl0001=1 # adults only",
+            "q01;hl0001=1 /* or */ | // employed\n q02;hl0012=1:3",
             "q02;hl0012 = hl0001",
             "q02;hl0012 = q01;hl001",
             "q01;hl0001=1 & q02;hl0012=3",
@@ -73,10 +266,26 @@ mod tests {
             "{q01;hl0001=1 & q02;hl0012=3}",
             "(q01;hl0001=1 & q02;hl0012=3) | q03;hl003=4",
             "(q01;hl0001=1 & q02;hl0012=3 & q02;hl0013=3) | q03;hl003=4",
+            "!q01;hl0001=1",
+            "!(q01;hl0001=1 | q02;hl0012=2)",
+            "![q01;hl0001=1] & !!{q02;hl0012=2}",
+            "q01;hl0001!=1 | !q02;hl0012=2",
+            "q01;elb0001>q02;elb0002/12",
+            "hl0001 = (hl0002 + 1) * 2",
+            "hl0001 = -hl0002",
+            "hl0001 = hl0002-1 & hl0003 = - 1",
+            "hl0001 >= 1.5 * -(q01;hl0002 - q02;hl0002)",
+            "hl0010 + hl0011 > 40",
+            "q05;pl0001 - q05;pl0002 >= 18",
+            "(hl0010 + hl0011) / 2 > 40 & (hl0012 * 2 = hl0013)",
+            "((hl0010 - 1) * 2 = 4)",
+            "-hl0001 < 2 | 2 * hl0001 = hl0002 - 1",
+            "q01;hl0001=1 and q02;hl0012<>3 or hl0003~=1",
+            "not (hl0001 =< 2 AND hl0002 => 4) & NOT missing(hl0003)",
         ];
 
         for case in cases {
-           let result = run(case, false);
+           let result = run(case, Options::default(), false);
            assert!(result.is_ok(), "Failed to parse valid input {:?}", case);
         }
     }
@@ -92,8 +301,46 @@ mod tests {
             "elb01;elb01;",
             "elb01;=2",
             "elb03 =",
-            "elb03 = 1:",
             "elb03 = 1,",
+            "elb03 = 1:3,",
+            "elb03 = 1:3:5",
+            "elb03 = :",
+            "elb03 = 1, :",
+            "elb03 = :hl0001",
+            "elb03 > 5:",
+            "elb03 >= 1,5:",
+            "elb03 < :5",
+            "elb03 <= 1,:5",
+            "pl0001 = 'DE",
+            "pl0001 < 'DE'",
+            "pl0001 >= 'DE', 'AT'",
+            "'DE' = pl0001",
+            "pl0001 = 'DE' + 1",
+            "pl0001 = 'D\\qE'",
+            "foo(hl0001) = 1",
+            "missing(hl0001) = 1",
+            "hl0001 = missing(hl0002)",
+            "missing(1)",
+            "missing(hl0001, hl0002)",
+            "abs(hl0001)",
+            "abs(hl0001, 1) = 1",
+            "min(hl0001) = 1",
+            "count() = 1",
+            "count(hl0001) = 1",
+            "max(hl0001, hl0002 = 1",
+            "any(hl0010..hl0025)",
+            "q01;hl0001=1 /* & q02;hl0012=1",
+            "// q01;hl0001=1",
+            "any() = 1",
+            "any(hl0010..) = 1",
+            "any(hl0010..pl0025) = 1",
+            "any(hl0025..hl0010) = 1",
+            "any(hl0010..hl025) = 1",
+            "any(hl0010 hl0011) = 1",
+            "hl00* = 1",
+            "hl0001 = hl00*",
+            "hl0001 = hl0002..hl0003",
+            "elb03 = 1,hl0001",
             "elb03 = 1.",
             "elb03 = -",
             "elb03 = -1-,2",
@@ -113,11 +360,46 @@ mod tests {
             "()",
             "(q01;hl0001=1  q02;hl0012=3) | q03;hl003=4",
             "q01;hl0001=1  (q02;hl0012=3 | q03;hl003=4)",
+            "!",
+            "!q01;hl0001",
+            "q01;hl0001=1 & !",
+            "q01;hl0001=!1",
+            "(q01;hl0001=1)!",
+            "hl0001 = 1 +",
+            "hl0001 = (1 + 2",
+            "hl0001 = 1 + * 2",
+            "hl0001 = ()",
+            "1 + 2 = hl0001",
+            "(hl0001 + 1) = ",
+            "(hl0001 + 1 = 2",
+            "q01-elb001 + 1 = 2",
+            "hl0001 = hl0002-hl0003",
+            "hl0001=1 and or hl0002=1",
+            "hl0001 ~ 1",
+            "hl0001 =! 1",
         ];
 
         for case in cases {
-            let result = run(case, false);
+            let result = run(case, Options::default(), false);
             assert!(result.is_err(), "Expected parse to fail. Input: {}, Got: {:?}", case, result);
         }
+
+        for case in ["hl0001=1 and hl0002=1", "hl0001 <> 1", "not hl0001=1"] {
+            let result = run(case, Options { strictness: Strictness::Strict, ..Options::default() }, false);
+            assert!(result.is_err(), "Expected strict parse to fail. Input: {}, Got: {:?}", case, result);
+        }
+
+        // Warnings only fail with --deny-warnings
+        for case in ["((hl0001=1))", "hl0001=1 | hl0002=1 & hl0003=1", "hl0001=1 or hl0002=1"] {
+            assert!(run(case, Options::default(), false).is_ok(), "Input: {}", case);
+            let result = run(case, Options { deny_warnings: true, ..Options::default() }, false);
+            assert!(result.is_err(), "Expected warnings to fail. Input: {}, Got: {:?}", case, result);
+        }
+
+        // Unknown items are warnings
+        let options = Options { catalog: Some(Catalog::from_lines("hl0001\nhl0002")), ..Options::default() };
+        assert!(run("hl0001=1 & hl0002=1", Options { deny_warnings: true, ..options.clone() }, false).is_ok());
+        assert!(run("hl0001=1 & hl002=1", options.clone(), false).is_ok());
+        assert!(run("hl0001=1 & hl002=1", Options { deny_warnings: true, ..options }, false).is_err());
     }
 }
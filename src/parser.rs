@@ -1,52 +1,110 @@
-/// A recursive descent parser for SOEP-style filter syntax.
-/// 
-/// Grammar of filter syntax:
-///
-/// grouping -> "(" or_group ")" | "[" or_group "]" | "{" or_group "}" 
-/// or_group ->  and_group ( "|" and_group )*
-/// and_group ->  primary ( "&" primary )*
-/// primary -> filter | grouping
-/// filter -> ( set ( "=" | "==" | "!=" | ">" | ">=" | "<" | "<=" ) ( set | NUMBER | range | list ) ) 
-/// set -> (( NUMBER | IDENTIFIER ) ";")? IDENTIFIER
-/// range -> NUMBER : NUMBER
-/// list -> NUMBER ("," NUMBER)+
-///
-/// Examples: "q01;elb0001=2", "elb0001=2:4", "q01;elb0001>=q02;elb0432", (q01;elb0001=1 &
-/// q02;elb0002=1)" 
-
-use crate::error::ParsingError;
-use crate::expr::Expr;
-use crate::scanner::Token;
+//! A recursive descent parser for SOEP-style filter syntax.
+//! 
+//! Grammar of filter syntax:
+//!
+//! grouping -> "(" or_group ")" | "[" or_group "]" | "{" or_group "}" 
+//! or_group ->  and_group ( "|" and_group )*
+//! and_group ->  unary ( "&" unary )*
+//! unary -> "!" unary | primary
+//! primary -> call | quantified | filter | grouping
+//! call -> IDENTIFIER "(" ( argument ( "," argument )* )? ")"
+//! argument -> or_group | arithmetic
+//! filter -> ( arithmetic ( "=" | "==" | "!=" | ">" | ">=" | "<" | "<=" ) ( range | list | STRING | arithmetic ) ) 
+//! set -> (( NUMBER | IDENTIFIER ) ";")? IDENTIFIER
+//! list -> element ( "," element )+
+//! element -> NUMBER | STRING | range
+//! range -> NUMBER ":" NUMBER? | ":" NUMBER
+//! arithmetic -> term ( ( "+" | "-" ) term )*
+//! term -> factor ( ( "*" | "/" ) factor )*
+//! factor -> "-" factor | atom
+//! atom -> NUMBER | call | set | "(" arithmetic ")"
+//! quantified -> ( "any" | "all" ) "(" selector ( "," selector )* ")" ( "=" | "==" | "!=" | ">" | ">=" | "<" | "<=" ) ( range | list | STRING | arithmetic )
+//! selector -> ( ( NUMBER | IDENTIFIER ) ";" )? ( IDENTIFIER ( ".." IDENTIFIER )? | PATTERN )
+//!
+//! Examples: "q01;elb0001=2", "elb0001=2:4", "q01;elb0001>=q02;elb0432", (q01;elb0001=1 &
+//! q02;elb0002=1)", "q01;elb0001>q02;elb0002/12", "hl0010 + hl0011 > 40",
+//! "count(hl0010=1, hl0011=1) >= 2", "!missing(hl0001)", "any(hl0010..hl0025, hl01*) = 1"
+//!
+//! Calls are checked against a `function::Registry`, which also decides whether an argument is
+//! parsed as filter (or_group) or as arithmetic operand.
+
+use std::collections::VecDeque;
+
+use crate::catalog;
+use crate::code::Code;
+use crate::error::{ParsingError, SecondaryLabel};
+use crate::function::{Registry, Type};
+use crate::lint::TokenLinter;
+use crate::expr::{Argument, Arithmetic, Bound, Comparison, Element, Expr, Ident, Logic, Operand, Quantifier, Selector, Value};
+use crate::scanner::{Literal, Token};
+use crate::span::Span;
 use crate::token_type::TokenType::{self, *};
 
-pub struct Parser<'a> {
-    tokens: &'a Vec<Token>,
-    current: usize,
-    errors: &'a mut Vec<ParsingError>,
+/// Parses a stream of tokens, e.g. from a `Scanner`. Tokens are consumed lazily with a
+/// lookahead of two tokens, or up to the matching ')' to tell arithmetic from groupings.
+pub struct Parser<'a, I> {
+    tokens: I,
+    lookahead: VecDeque<Token<'a>>, // current and next token, more when looking ahead
+    previous: Option<Token<'a>>,
+    linter: TokenLinter,
+    errors: Vec<ParsingError>,
     pub had_error: bool,
     synchronized: bool,
+    functions: Registry,
 }
 
-impl<'a> Parser<'a> {
+impl<'a, I> Parser<'a, I>
+where
+    I: Iterator<Item = Result<Token<'a>, ParsingError>>,
+{
     
-    pub fn new(tokens: &'a Vec<Token>, errors: &'a mut Vec<ParsingError>, scanner_had_error: bool) -> Self {
-        Parser {
-            tokens: tokens,
-            current: 0,
-            errors: errors,
-            had_error: scanner_had_error,
+    /// Creates a parser for filters calling the built-in functions.
+    pub fn new(tokens: I) -> Self {
+        Self::with_functions(tokens, Registry::builtins())
+    }
+
+    /// Creates a parser for filters calling the functions in `functions`.
+    pub fn with_functions(tokens: I, functions: Registry) -> Self {
+        let mut parser = Parser {
+            tokens,
+            lookahead: VecDeque::with_capacity(2),
+            previous: None,
+            linter: TokenLinter::default(),
+            errors: Vec::new(),
+            had_error: false,
             synchronized: false,
-        }
+            functions,
+        };
+        parser.fill();
+        parser
     }
 
-    /// Parses all tokens to return a syntax tree. Encounterd errors are stored in `self.errors`.
+    /// Returns all errors of the scanner and the parser, including internal errors.
+    pub fn errors(&self) -> &[ParsingError] {
+        &self.errors
+    }
+
+    /// Returns the warnings of the tokens taken from the token stream so far, e.g. alternative
+    /// spellings of operators. After `parse`, these are the warnings of all tokens.
+    pub fn warnings(&self) -> Vec<ParsingError> {
+        self.linter.warnings()
+    }
+
+    pub fn into_errors(self) -> Vec<ParsingError> {
+        self.errors
+    }
+
+    /// Parses all tokens to return a syntax tree. Encountered errors, including those of the
+    /// scanner, are stored in `self.errors`.
     pub fn parse(&mut self) -> Result<Expr, ParsingError> {
        let mut result = None;
         while !self.at_end() {
           if let Ok(expr) = self.or_group() {
               if !self.at_end() & !self.synchronized {
                   // Case:  Missing '&' or '|'. Ex.: 'q01;elb001=1 q02;elb002=2'
-                 return Err(self.error("Expected '&' or '|'".to_string(), true)) 
+                 let error = self.missing_logical_operator();
+                 self.drain();
+                 return Err(error)
                 // Case: Success
                 } else {
                     result = Some(expr);    
@@ -58,11 +116,38 @@ impl<'a> Parser<'a> {
             }
         }
 
-        let error = ParsingError::Report { message: "Parsing Error".to_string(), line: 1, column: 1 };
-        if self.had_error {
-           return Err(error) 
-        } else {
-            return result.ok_or(error)
+        match result {
+            Some(expr) if !self.had_error => Ok(expr),
+            _ => Err(self.first_error()),
+        }
+    }
+
+    /// Reports a missing '&' or '|' after a filter. Suggests ':' or ',' for numbers joined by
+    /// '_', ex.: 'hl0001=2_3', which is scanned as item '2_' followed by '3'.
+    fn missing_logical_operator(&mut self) -> ParsingError {
+        let (previous, next) = (self.previous().clone(), self.peek().clone());
+        let message = "Expected '&' or '|'".to_string();
+        let joined = previous.span.end == next.span.start && next.variant == Number;
+        match previous.lexeme.strip_suffix('_') {
+            Some(number) if joined && number.chars().all(|c| c.is_ascii_digit()) => {
+                let error = self.error_at(Code::MissingLogicalOperator, message, previous.span.to(next.span), Vec::new());
+                let help = format!("Use ':' for a range, '{0}:{1}', or ',' for a list, '{0},{1}'", number, next.lexeme);
+                self.suggest(error, help)
+            },
+            _ => {
+                let error = self.error(Code::MissingLogicalOperator, message);
+                self.suggest(error, format!("Insert '&' or '|' before '{}'", next.lexeme))
+            },
+        }
+    }
+
+    /// Returns the first error reported to the user. Reports a new error if parsing failed
+    /// without one, e.g. for empty input.
+    fn first_error(&mut self) -> ParsingError {
+        let reported = self.errors.iter().find(|error| matches!(error, ParsingError::Report { .. }));
+        match reported {
+            Some(error) => error.clone(),
+            None => self.error(Code::ExpectedFilter, "Expected filter expression".to_string()),
         }
     }
     
@@ -86,18 +171,18 @@ impl<'a> Parser<'a> {
 
         // Match '(' 
         if let Ok(expr) = self.consume(LeftParen, RightParen, ')') {
-            return Ok(expr)
+            Ok(expr)
         // '['
         } else if let Ok(expr) = self.consume(LeftBracket, RightBracket, ']') { 
-            return Ok(expr)
+            Ok(expr)
         // '{'
         } else if let Ok(expr) = self.consume(LeftBrace, RightBrace, '}') {
-            return Ok(expr)
+            Ok(expr)
         // No parentheses
         } else {
             let msg = format!("Did not expect '{}'", self.peek().lexeme);
-            Err(self.error(msg, !self.synchronized)) // suppress error for users if parser in
-                                                     // synchronized state
+            // suppress error for users if parser in synchronized state
+            if self.synchronized { Err(self.internal(msg)) } else { Err(self.error(Code::ExpectedFilter, msg)) }
         }
     }
     
@@ -106,15 +191,18 @@ impl<'a> Parser<'a> {
     /// Ex.: "(q01;hl001=1 | q02;hl002=2)"
     fn consume(&mut self, left_paren: TokenType, right_paren: TokenType, expect: char) -> Result<Expr, ParsingError> {
         if self.match_token(&[left_paren]) {
+            let open = self.previous().span;
             let expr = self.or_group()?;
             if self.match_token(&[right_paren]) {
-                return Ok(Expr::Grouping { expr: Box::new(expr) })
+                let span = open.to(self.previous().span);
+                Ok(Expr::Grouping { expr: Box::new(expr), span })
             } else {
                 let msg = format!("Expected '{}'", expect);
-                return Err(self.error(msg, true))
+                let span = self.peek().span;
+                Err(self.error_at(Code::UnclosedBracket, msg, span, vec![unclosed(open)]))
             }
         } else {
-            return Err(self.error("Not a parenthesized expression".to_string(), false))
+            Err(self.internal("Not a parenthesized expression".to_string()))
         }
     }
 
@@ -122,157 +210,532 @@ impl<'a> Parser<'a> {
     fn or_group(&mut self) -> Result<Expr, ParsingError> {
         let mut left = self.and_group()?;
         while self.match_token(&[Or]) {
-            let operator = self.previous(); 
             if let Ok(right) = self.and_group() {
-                left = Expr::Logical { left: Box::new(left), operator: operator, right: Box::new(right) };
+                let span = left.span().to(right.span());
+                left = Expr::Logical { left: Box::new(left), operator: Logic::Or, right: Box::new(right), span };
             } else {
-                return Err(self.error("Expected filter expression".to_string(), true));
+                return Err(self.error(Code::ExpectedFilter, "Expected filter expression".to_string()));
             }
         }
         Ok(left)
     }
 
-    /// Matches production: and_group -> primary ( "&" primary )* 
+    /// Matches production: and_group -> unary ( "&" unary )* 
     fn and_group(&mut self) -> Result<Expr, ParsingError> {
-        let mut left = self.primary()?;
+        let mut left = self.unary()?;
         while self.match_token(&[And]) {
-            let operator = self.previous();
-            if let Ok(right) = self.and_group() {
-                left = Expr::Logical { left: Box::new(left), operator: operator, right: Box::new(right) }; 
+            if let Ok(right) = self.unary() {
+                let span = left.span().to(right.span());
+                left = Expr::Logical { left: Box::new(left), operator: Logic::And, right: Box::new(right), span }; 
             } else {
-                return Err(self.error("Expected filter expression".to_string(), true));
+                return Err(self.error(Code::ExpectedFilter, "Expected filter expression".to_string()));
             }
         }
        Ok(left) 
     }
 
-    /// Matches production: primary -> filter | grouping
+    /// Matches production: unary -> "!" unary | primary
+    /// Ex.: "!q01;hl001=1" or "!(q01;hl001=1 | q02;hl002=2)"
+    fn unary(&mut self) -> Result<Expr, ParsingError> {
+        if self.match_token(&[Bang]) {
+            let bang = self.previous().span;
+            if let Ok(expr) = self.unary() {
+                Ok(Expr::Not { span: bang.to(expr.span()), expr: Box::new(expr) })
+            } else {
+                Err(self.error(Code::ExpectedFilter, "Expected filter or one of '(', '[', '{' after '!'".to_string()))
+            }
+        } else {
+            self.primary()
+        }
+    }
+
+    /// Matches production: primary -> call | quantified | filter | grouping
     fn primary(&mut self) -> Result<Expr, ParsingError> {
         if let Ok(filter) = self.filter() {
-            return Ok(filter)
+            Ok(filter)
         } else if let Ok(grouping) = self.grouping() {
-            return Ok(grouping)
+            Ok(grouping)
         } else {
-            return Err(self.error("Expected filter or one of '(', '[', '{'".to_string(), !self.synchronized))
+            let msg = "Expected filter or one of '(', '[', '{'".to_string();
+            if self.synchronized { Err(self.internal(msg)) } else { Err(self.error(Code::ExpectedFilter, msg)) }
         }
     }
 
-    /// Matches the production: filter -> ( set ( "=" | "==" | "!=" | ">" | ">=" | "<" | "<=" ) ( set | NUMBER | range | list ) ) 
-    /// Ex.: 'q02;elb0003>1' or 'elb0002=1' or '02;elb0002!=elb0001'
+    /// Matches the production: filter -> ( arithmetic ( "=" | "==" | "!=" | ">" | ">=" | "<" | "<=" ) ( range | list | STRING | arithmetic ) ) 
+    /// Ex.: 'q02;elb0003>1' or 'elb0002=1' or '02;elb0002!=elb0001' or 'elb0002>elb0001*12' or
+    /// 'q05;pl0001 - q05;pl0002 >= 18'
     fn filter(&mut self) -> Result<Expr, ParsingError> {
 
-        // Match left hand side, ex: q04;elb0003
-        let set = self.set()?;
+        // Match call of a function returning a boolean, ex.: missing(hl0001)
+        if self.check_call() && self.functions.get(self.peek().lexeme).is_some_and(|function| function.result == Type::Boolean) {
+            let (name, arguments, span) = self.call()?;
+            if self.check_token(&[Equal, EqualEqual, BangEqual, Greater, GreaterEqual, Less, LessEqual]) {
+                return Err(self.error_at(Code::ArgumentType, format!("Function '{}' returns a filter and can not be compared", name), span, Vec::new()))
+            }
+            return Ok(Expr::Call { name, arguments, span })
+        }
+
+        // Match quantified items, ex.: any(hl0010..hl0025) = 1
+        if self.check_call() && matches!(self.peek().lexeme, "any" | "all") {
+            return self.quantified()
+        }
 
-        // Match operator
-        let operator = if self.match_token(&[Equal, Equal, EqualEqual, BangEqual, Greater, GreaterEqual, Less, LessEqual]) {
-            self.previous()
+        // Match left hand side, ex: q04;elb0003 or (hl0010 + hl0011) / 2
+        if self.check(&Str) {
+            return Err(self.error(Code::MissingItem, "Expected item on the left-hand side of comparison".to_string()))
+        }
+        let starts_operand = self.check_token(&[Identifier, Number, Minus, Pattern])
+            || (self.check(&LeftParen) && self.is_parenthesized_operand());
+        if !starts_operand {
+            // allowed to fail because primary() matches grouping() after filter()
+            return Err(self.internal("Expected item identifier".to_string()))
+        }
+        let set = self.arithmetic()?;
+        if !Self::has_item(&set) {
+            let span = set.span();
+            return Err(self.error_at(Code::MissingItem, "Expected item on the left-hand side of comparison".to_string(), span, Vec::new()))
+        }
+
+        let operator = self.operator().map_err(|error| self.suggest_separator(error, &set))?;
+        let right = self.right(operator)?;
+        Ok(Expr::Filter { span: set.span().to(right.span()), left: set, operator, right })
+    }
+
+    /// Matches the comparison operator of a filter.
+    fn operator(&mut self) -> Result<Comparison, ParsingError> {
+        if self.match_token(&[Equal, EqualEqual, BangEqual, Greater, GreaterEqual, Less, LessEqual]) {
+            Ok(Self::comparison(&self.previous().variant))
         } else {
-            return Err(self.error("Expected one of '=', '==', '!=', '>', '>=', '<', '<='".to_string(), true))
+            Err(self.error(Code::ExpectedComparison, "Expected one of '=', '==', '!=', '>', '>=', '<', '<='".to_string()))
+        }
+    }
+
+    /// Suggests ';' for a missing comparison after an item followed by ':' and another item.
+    /// Ex.: 'q01:hl0001' instead of 'q01;hl0001'
+    fn suggest_separator(&mut self, error: ParsingError, left: &Operand) -> ParsingError {
+        match left {
+            Operand::Item { question: None, item: question, .. } if self.check(&Colon) && self.check_next(&[Identifier]) => {
+                let help = format!("Use ';' to separate question and item: '{};{}'", question, self.lookahead[1].lexeme);
+                self.suggest(error, help)
+            },
+            _ => error,
+        }
+    }
+
+    /// Matches the right-hand side of a filter: ( range | list | STRING | arithmetic )
+    fn right(&mut self, operator: Comparison) -> Result<Operand, ParsingError> {
+        // Match range or list, ex.: 1:3, :5, -2,-1,3: or 'DE','AT'
+        if self.check(&Colon) || (self.check_token(&[Number, Str]) && self.check_next(&[Colon, Comma])) {
+            self.list(operator)
+
+        // Match string, ex.: 'DE'
+        } else if self.check(&Str) {
+            let (value, span) = self.text(operator)?;
+            Ok(Operand::Text { value, span })
+
+        // Match arithmetic expression, incl. single items and numbers. Patterns are reported
+        // by atom()
+        } else if self.check_token(&[Identifier, Number, Minus, LeftParen, Pattern]) {
+            self.arithmetic()
+        } else {
+            Err(self.error(Code::ExpectedValue, "Expected number, string, list, range, or item".to_string()))
+        }
+    }
+
+    /// Matches the production:
+    /// quantified -> ( "any" | "all" ) "(" selector ( "," selector )* ")" ( "=" | "==" | "!=" | ">" | ">=" | "<" | "<=" ) ( range | list | STRING | arithmetic )
+    /// Ex.: "any(hl0010..hl0025) = 1" or "all(q01;hl00*, hl0100) != -1"
+    fn quantified(&mut self) -> Result<Expr, ParsingError> {
+        self.advance();
+        let start = self.previous().span;
+        let quantifier = if self.previous().lexeme == "any" { Quantifier::Any } else { Quantifier::All };
+        self.advance(); // consume '('
+        let open = self.previous().span;
+
+        let mut items = vec![self.selector()?];
+        while self.match_token(&[Comma]) {
+            items.push(self.selector()?);
+        }
+        if !self.match_token(&[RightParen]) {
+            let span = self.peek().span;
+            return Err(self.error_at(Code::ExpectedComma, "Expected ',' or ')' after item".to_string(), span, vec![unclosed(open)]))
+        }
+
+        let operator = self.operator()?;
+        let right = self.right(operator)?;
+        Ok(Expr::Quantified { span: start.to(right.span()), quantifier, items, operator, right })
+    }
+
+    /// Matches the production:
+    /// selector -> ( ( NUMBER | IDENTIFIER ) ";" )? ( IDENTIFIER ( ".." IDENTIFIER )? | PATTERN )
+    /// Ex.: "hl0010", "q01;hl0010..hl0025" or "hl00*"
+    fn selector(&mut self) -> Result<Selector, ParsingError> {
+        let question = if self.check_next(&[SemiColon]) && self.match_token(&[Identifier, Number]) {
+            let question = self.ident();
+            self.advance(); // consume ';'
+            Some(question)
+        } else {
+            None
         };
-        // Match right hand side
-        // Match range
-        if let Ok(expr) = self.range() {
-            return Ok(Expr::Filter { left: Box::new(set), operator: operator, right: Box::new(expr) })
-
-        // Match list
-        } else if self.check_next(&[Comma]) {
-            if let Ok(expr) = self.list() {
-                return Ok(Expr::Filter { left: Box::new(set), operator: operator, right: Box::new(expr) })
-            } else {
-                return Err(self.error("list() failed in filter()".to_string(), false))
+
+        if self.match_token(&[Pattern]) {
+            let pattern = self.ident();
+            let span = question.as_ref().map_or(pattern.span, |question| question.span).to(pattern.span);
+            return Ok(Selector::Pattern { question, pattern, span })
+        }
+        if !self.match_token(&[Identifier]) {
+            return Err(self.error(Code::ExpectedSelector, "Expected item, item range or item pattern".to_string()))
+        }
+        let item = self.ident();
+        let first = question.as_ref().map_or(item.span, |question| question.span);
+        if !self.match_token(&[DotDot]) {
+            return Ok(Selector::Item { span: first.to(item.span), question, item })
+        }
+
+        if !self.check(&Identifier) {
+            return Err(self.error(Code::ExpectedSelector, "Expected item after '..'".to_string()))
+        }
+        if let Err(message) = catalog::check_item_range(&item.name, self.peek().lexeme) {
+            let span = self.peek().span;
+            let start = SecondaryLabel { message: "Range starts here".to_string(), span: item.span };
+            return Err(self.error_at(Code::InvalidItemRange, message, span, vec![start]))
+        }
+        self.advance();
+        let end = self.ident();
+        Ok(Selector::Range { span: first.to(end.span), question, start: item, end })
+    }
+
+    /// Maps comparison tokens to `Comparison`. '=' and '==' are equivalent.
+    fn comparison(token_type: &TokenType) -> Comparison {
+        match token_type {
+            BangEqual => Comparison::NotEqual,
+            Greater => Comparison::Greater,
+            GreaterEqual => Comparison::GreaterEqual,
+            Less => Comparison::Less,
+            LessEqual => Comparison::LessEqual,
+            _ => Comparison::Equal,
+        }
+    }
+
+    /// Returns true if the operand contains an item, ex.: "-(q01;hl0001 + 1)".
+    fn has_item(operand: &Operand) -> bool {
+        match operand {
+            Operand::Item { .. } => true,
+            Operand::Binary { left, right, .. } => Self::has_item(left) || Self::has_item(right),
+            Operand::Negative { operand, .. } | Operand::Grouping { operand, .. } => Self::has_item(operand),
+            Operand::Call { arguments, .. } => arguments.iter().any(|argument| match argument {
+                Argument::Filter(_) => true,
+                Argument::Operand(operand) => Self::has_item(operand),
+            }),
+            Operand::Number { .. } | Operand::Text { .. } | Operand::Range { .. } | Operand::List { .. } => false,
+        }
+    }
+
+    /// Returns true if the '(' at the current token opens an arithmetic operand, ex.:
+    /// "(hl0010 + hl0011) > 40", rather than a grouping of filters, ex.: "(hl0010 > 40)". Looks
+    /// ahead until the matching ')'. Arguments of calls may contain filters, ex.:
+    /// "(count(hl0010=1, hl0011=1) + 1) > 2".
+    fn is_parenthesized_operand(&mut self) -> bool {
+        let mut calls = Vec::new(); // for each open '(', whether it encloses call arguments
+        let mut position = 0;
+        loop {
+            self.fill_to(position + 1);
+            let Some(token) = self.lookahead.get(position) else { return false };
+            match token.variant {
+                LeftParen => calls.push(position > 0 && self.lookahead[position - 1].variant == Identifier),
+                RightParen => {
+                    calls.pop();
+                    if calls.is_empty() {
+                        return position > 1
+                    }
+                },
+                Identifier | Number | SemiColon | Plus | Minus | Star | Slash => (),
+                EOF => return false,
+                _ if calls.last() == Some(&true) => (),
+                _ => return false,
             }
-        // Match set
-        } else if self.check(&Identifier) || self.check_next(&[SemiColon]) {
-            if let Ok(expr) = self.set() {
-                return Ok(Expr::Filter { left: Box::new(set), operator: operator, right: Box::new(expr) })
-            } else {
-                return Err(self.error("set() failed in filter()".to_string(), false))
+            position += 1;
+        }
+    }
+
+    /// Matches production: arithmetic -> term ( ( "+" | "-" ) term )*
+    /// Ex.: "q01;hl0001 + hl0002 * 2"
+    fn arithmetic(&mut self) -> Result<Operand, ParsingError> {
+        let mut left = self.term()?;
+        while self.match_token(&[Plus, Minus]) {
+            let operator = if self.previous().variant == Plus { Arithmetic::Add } else { Arithmetic::Subtract };
+            // Case: '-' between identifiers without spaces. Ex.: 'q01-hl0001' instead of 'q01;hl0001'
+            let minus = self.previous().span;
+            if operator == Arithmetic::Subtract && matches!(left, Operand::Item { .. }) && left.span().end == minus.start
+                && self.check(&Identifier) && self.peek().span.start == minus.end {
+                let error = self.error(Code::AmbiguousMinus, "Expected ';' or spaces around '-' between identifiers".to_string());
+                let item = self.peek().lexeme.to_string();
+                return Err(match &left {
+                    Operand::Item { question: None, item: question, .. } => {
+                        let help = format!("Write '{0};{1}' for item '{1}' of question '{0}', or '{0} - {1}' to subtract", question, item);
+                        self.suggest(error, help)
+                    },
+                    _ => error,
+                })
             }
-        // Match number
-        } else if self.match_token(&[Number]) {
-           let number = self.previous();
-           return Ok(Expr::Filter { left: Box::new(set), operator: operator, right: Box::new(Expr::Literal {value: number }) })
+            let right = self.term()?;
+            left = Operand::Binary { span: left.span().to(right.span()), left: Box::new(left), operator, right: Box::new(right) };
+        }
+        Ok(left)
+    }
+
+    /// Matches production: term -> factor ( ( "*" | "/" ) factor )*
+    fn term(&mut self) -> Result<Operand, ParsingError> {
+        let mut left = self.factor()?;
+        while self.match_token(&[Star, Slash]) {
+            let operator = if self.previous().variant == Star { Arithmetic::Multiply } else { Arithmetic::Divide };
+            let right = self.factor()?;
+            left = Operand::Binary { span: left.span().to(right.span()), left: Box::new(left), operator, right: Box::new(right) };
+        }
+        Ok(left)
+    }
+
+    /// Matches production: factor -> "-" factor | atom
+    /// A minus directly before a number gives a negative number. Ex.: "- 1" is the same as "-1".
+    fn factor(&mut self) -> Result<Operand, ParsingError> {
+        if self.match_token(&[Minus]) {
+            let minus = self.previous().span;
+            if self.check(&Number) && !self.check_next(&[SemiColon]) {
+                self.advance();
+                let number = self.value()?;
+                let span = minus.to(number.span);
+                let Some(value) = number.number.checked_neg() else {
+                    let error = self.error_at(Code::InvalidNumber, "Invalid number".to_string(), span, Vec::new());
+                    return Err(self.suggest(error, "The negated number is too large for a 64-bit integer".to_string()))
+                };
+                return Ok(Operand::Number { value, span })
+            }
+            let operand = self.factor()?;
+            Ok(Operand::Negative { span: minus.to(operand.span()), operand: Box::new(operand) })
         } else {
-            return Err(self.error("Expected number, list of numbers, range, or item".to_string(), true))
+            self.atom()
         }
     }
 
-    /// Matches production: range -> NUMBER ":" NUMBER
-    /// Ex.: "1:5"
-    fn range(&mut self) -> Result<Expr, ParsingError> {
-        // Check next token before consuming anything
-        if self.check_next(&[Colon]) {
-            
-            // Match left number
-            if self.match_token(&[Number]) {
-                let number_left = self.previous(); 
-                self.advance(); // consume ':'
-
-                // Match right numbner
-                if self.match_token(&[Number]) {
-                    let number_right = self.previous();
-                    return Ok(Expr::Range{ left: number_left, right: number_right })
-                } else {
-                    return Err(self.error("Expected number".to_string(), true))
-                } 
+    /// Matches production: atom -> NUMBER | call | set | "(" arithmetic ")"
+    fn atom(&mut self) -> Result<Operand, ParsingError> {
+        if self.check_call() {
+            if self.functions.get(self.peek().lexeme).is_some_and(|function| function.result == Type::Boolean) {
+                return Err(self.error(Code::ArgumentType, format!("Function '{}' returns a filter, expected a number", self.peek().lexeme)))
+            }
+            let (name, arguments, span) = self.call()?;
+            Ok(Operand::Call { name, arguments, span })
+        } else if self.check(&Identifier) || self.check_next(&[SemiColon]) {
+            self.set()
+        } else if self.match_token(&[Number]) {
+            let number = self.value()?;
+            Ok(Operand::Number { value: number.number, span: number.span })
+        } else if self.match_token(&[LeftParen]) {
+            let open = self.previous().span;
+            let operand = self.arithmetic()?;
+            if self.match_token(&[RightParen]) {
+                Ok(Operand::Grouping { span: open.to(self.previous().span), operand: Box::new(operand) })
             } else {
-                return Err(self.error("Expected number".to_string(), true))
+                let span = self.peek().span;
+                Err(self.error_at(Code::UnclosedBracket, "Expected ')'".to_string(), span, vec![unclosed(open)]))
             }
+        } else if self.check(&Pattern) {
+            Err(self.error(Code::MisplacedPattern, "Item patterns are only allowed in any() and all()".to_string()))
         } else {
-            Err(self.error("No ':' in range()".to_string(), false)) // Non-reporting error
-        }
-    }
-         
-    /// Matches production: list -> NUMBER ( "," NUMBER )*
-    /// Ex.: "2,4,10"
-    /// Caution: Function also matches a single number ( list -> NUMBER )
-    fn list(&mut self) -> Result<Expr, ParsingError> {
-       if self.match_token(&[Number]) {
-            let value = self.previous();
-            if self.match_token(&[Comma]) {
-                let list = self.list()?;
-                return Ok( Expr::List { value: value, next: Box::new(list) })
-            } else {
-                return Ok( Expr::List { value: value, next: Box::new(Expr::EndOfList ) })
+            Err(self.error(Code::ExpectedOperand, "Expected number, item, or '('".to_string()))
+        }
+    }
+
+    /// Matches production: call -> IDENTIFIER "(" ( argument ( "," argument )* )? ")"
+    /// Ex.: "count(hl0010=1, hl0011=1)" or "abs(hl0001 - 2)"
+    fn call(&mut self) -> Result<(Ident, Vec<Argument>, Span), ParsingError> {
+        let Some(function) = self.functions.get(self.peek().lexeme).cloned() else {
+            return Err(self.error(Code::UnknownFunction, format!("Unknown function '{}'", self.peek().lexeme)))
+        };
+        self.advance();
+        let name = self.ident();
+        self.advance(); // consume '('
+        let open = self.previous().span;
+
+        let mut arguments = Vec::new();
+        if !self.check(&RightParen) {
+            loop {
+                let Some(parameter) = function.parameter(arguments.len()) else {
+                    return Err(self.error(Code::ArgumentCount, format!("Too many arguments, '{}' expects {}", name, function.arity())))
+                };
+                arguments.push(self.argument(&name, parameter)?);
+                if !self.match_token(&[Comma]) {
+                    break
+                }
             }
+        }
+
+        if !self.check(&RightParen) {
+            let span = self.peek().span;
+            return Err(self.error_at(Code::ExpectedComma, "Expected ',' or ')' after argument".to_string(), span, vec![unclosed(open)]))
+        }
+        if arguments.len() < function.parameters.len() {
+            let span = name.span.to(self.peek().span);
+            return Err(self.error_at(Code::ArgumentCount, format!("'{}' expects {}, got {}", name, function.arity(), arguments.len()), span, Vec::new()))
+        }
+        self.advance(); // consume ')'
+        Ok((name.clone(), arguments, name.span.to(self.previous().span)))
+    }
+
+    /// Matches production: argument -> or_group | arithmetic
+    /// Filter parameters take an or_group, item parameters a set and number parameters an
+    /// arithmetic expression.
+    fn argument(&mut self, function: &Ident, parameter: Type) -> Result<Argument, ParsingError> {
+        match parameter {
+            Type::Boolean => self.or_group().map(Argument::Filter),
+            Type::Item if self.check(&Identifier) || self.check_next(&[SemiColon]) => self.set().map(Argument::Operand),
+            Type::Item => Err(self.error(Code::ArgumentType, format!("Expected item as argument of '{}'", function))),
+            Type::Number => self.arithmetic().map(Argument::Operand),
+        }
+    }
+
+    /// Returns true if the current token is the name of a function call, ex.: "abs" in "abs(-1)".
+    fn check_call(&self) -> bool {
+        self.check(&Identifier) && self.check_next(&[LeftParen])
+    }
+
+    /// Matches production: list -> element ( "," element )+
+    /// Ex.: "2,4,10" or "-2,-1,3:5,8"
+    /// Caution: Function also matches a single element, which gives `Operand::Range`,
+    /// `Operand::Number` or `Operand::Text`.
+    fn list(&mut self, operator: Comparison) -> Result<Operand, ParsingError> {
+        let text = self.check(&Str);
+        let mut elements = vec![self.element(operator)?];
+        // A comma before an identifier, a bracket or a question separates arguments of a call,
+        // ex.: "count(hl0001=1,2, hl0002=1)" or "count(hl0001=1, 02;hl0002=1)"
+        while self.check(&Comma) && !self.check_next(&[Identifier, Bang, LeftParen, LeftBracket, LeftBrace]) && !self.next_is_question() {
+            self.advance();
+            // Elements have the type of the first element, ex.: not "'DE',1"
+            if self.check(&Str) != text {
+                let expected = if text { "string" } else { "number or range" };
+                let span = self.peek().span;
+                let first = SecondaryLabel { message: "List starts here".to_string(), span: elements[0].span() };
+                return Err(self.error_at(Code::MixedListTypes, format!("Expected {} like the first element of the list", expected), span, vec![first]))
+            }
+            elements.push(self.element(operator)?);
+        }
+
+        if elements.len() > 1 {
+            let span = elements[0].span().to(elements[elements.len() - 1].span());
+            return Ok(Operand::List { elements, span })
+        }
+        match elements.remove(0) {
+            Element::Value(value) => Ok(Operand::Number { value: value.number, span: value.span }),
+            Element::Text { value, span } => Ok(Operand::Text { value, span }),
+            Element::Range { start, end, span } => Ok(Operand::Range { start, end, span }),
+        }
+    }
+
+    /// Matches production: element -> NUMBER | range
+    /// range -> NUMBER ":" NUMBER? | ":" NUMBER
+    /// Ex.: "2", "1:5", "3:" or ":5"
+    /// Open ranges are rejected where they make the comparison always false. A list is greater
+    /// than its largest value and less than its smallest value, so '>' and '>=' require upper
+    /// bounds, '<' and '<=' lower bounds.
+    fn element(&mut self, operator: Comparison) -> Result<Element, ParsingError> {
+        if self.check(&Str) {
+            let (value, span) = self.text(operator)?;
+            return Ok(Element::Text { value, span })
+        }
+        let start = if self.match_token(&[Number]) {
+            let value = self.value()?;
+            if !self.check(&Colon) {
+                return Ok(Element::Value(value))
+            }
+            Bound::Included(value)
+        } else if self.check(&Colon) {
+            Bound::Unbounded
         } else {
-            Err(self.error("Expected number".to_string(), true))
+            return Err(self.error(Code::InvalidListElement, "Expected number".to_string()))
+        };
+
+        // Check bounds before consuming ':', so that errors point to it
+        let has_end = self.check_next(&[Number]);
+        if start == Bound::Unbounded && !has_end {
+            return Err(self.error(Code::InvalidListElement, "Expected number before or after ':'".to_string()))
+        }
+        let required = match operator {
+            Comparison::Greater | Comparison::GreaterEqual if !has_end => Some("upper"),
+            Comparison::Less | Comparison::LessEqual if start == Bound::Unbounded => Some("lower"),
+            _ => None,
+        };
+        if let Some(required) = required {
+            return Err(self.error(Code::OpenRange, format!("Expected {} bound of range compared with '{}'", required, operator)))
+        }
+
+        self.advance(); // consume ':'
+        let colon = self.previous().span;
+        let end = if self.match_token(&[Number]) { Bound::Included(self.value()?) } else { Bound::Unbounded };
+        let span = start.value().map_or(colon, |value| value.span).to(end.value().map_or(colon, |value| value.span));
+        Ok(Element::Range { start, end, span })
+    }
+
+    /// Matches a STRING token and returns its value. Strings can only be compared with '=' and
+    /// '!=', as there is no order of text values.
+    fn text(&mut self, operator: Comparison) -> Result<(String, Span), ParsingError> {
+        if !matches!(operator, Comparison::Equal | Comparison::NotEqual) {
+            return Err(self.error(Code::StringComparison, format!("Strings can only be compared with '=' or '!=', not '{}'", operator)))
+        }
+        self.advance();
+        let token = self.previous();
+        match &token.literal {
+            Some(Literal::Str(value)) => Ok((value.clone(), token.span)),
+            _ => Err(self.internal("String token without value".to_string())),
         }
     }
 
+    /// Converts the previous NUMBER token into a `Value`.
+    fn value(&mut self) -> Result<Value, ParsingError> {
+        let token = self.previous();
+        match token.literal {
+            Some(Literal::Number(number)) => Ok(Value { number, span: token.span }),
+            _ => Err(self.internal("Number token without value".to_string())),
+        }
+    }
+
+    /// Converts the previous token into an `Ident`.
+    fn ident(&self) -> Ident {
+        let token = self.previous();
+        Ident { name: token.lexeme.to_string(), span: token.span }
+    }
+
     /// Matches the production:  set → (( NUMBER | IDENTIFIER )+ ";")? IDENTIFIER
     /// Ex: 'q01;elb0001' or 'elb0001'
-    fn set(&mut self) -> Result<Expr, ParsingError> {
+    fn set(&mut self) -> Result<Operand, ParsingError> {
         // Case with ';', ex.: q01;elb001
         if self.check_next(&[SemiColon]) {
             
             // match question
             if self.match_token(&[Identifier, Number]) {
-                let question = self.previous();
+                let question = self.ident();
                 self.advance(); // consume ';'
                                 
                 // match item
                 if self.match_token(&[Identifier]) {
-                    let item = self.previous();
-                    return Ok(Expr::Set { question: Some(question), item: item });
+                    let item = self.ident();
+                    Ok(Operand::Item { span: question.span.to(item.span), question: Some(question), item })
                 } else {
-                    return Err(self.error("Expected item identifier".to_string(), true));
+                    Err(self.error(Code::ExpectedIdentifier, "Expected item identifier".to_string()))
                 }
             } else {
-                return Err(self.error("Expected question identifier".to_string(), true));
+                Err(self.error(Code::ExpectedIdentifier, "Expected question identifier".to_string()))
             }
 
         // Case without ';', ex.: elb001
         } else {
             // match item
             if self.match_token(&[Identifier]) {
-                let item = self.previous();
-                return Ok(Expr::Set { question: None, item: item });
+                let item = self.ident();
+                Ok(Operand::Item { span: item.span, question: None, item })
             } else {
                 // allowed to fail because primary() matches grouping() after filter(). Rewrite in
                 // update
-                return Err(self.error("Expected item identifier".to_string(), false));
+                Err(self.internal("Expected item identifier".to_string()))
             }
         }
     }
@@ -295,7 +758,6 @@ impl<'a> Parser<'a> {
         }
     }
 
-    #[allow(dead_code)]
     fn check_token(&self, token_types: &[TokenType]) -> bool {
         for token_type in token_types {
             if self.check(token_type) {
@@ -309,56 +771,124 @@ impl<'a> Parser<'a> {
         if self.next_is_end() {
             false
         } else {
-            for token_type in token_types {
-                if self.tokens[self.current + 1].variant == *token_type {
-                    return true
-                }
-            }
-            false
+            token_types.contains(&self.lookahead[1].variant)
         }
     }
 
+    /// Returns true if the token after the current one starts a question number followed by ';',
+    /// ex.: "02;" or "-1;".
+    fn next_is_question(&mut self) -> bool {
+        let position = if self.check_next(&[Minus]) { 3 } else { 2 };
+        self.fill_to(position + 1);
+        self.lookahead.get(position - 1).is_some_and(|token| token.variant == Number)
+            && self.lookahead.get(position).is_some_and(|token| token.variant == SemiColon)
+    }
+
     fn advance(&mut self) {
         if !self.at_end() {
-            self.current += 1;
+            self.previous = self.lookahead.pop_front();
+            self.fill();
         }
-        //self.previous()
     }
 
-    fn peek(&self) -> &Token {
-        &self.tokens[self.current]
+    /// Pulls tokens from the token stream until the current and the next token are buffered.
+    fn fill(&mut self) {
+        self.fill_to(2);
     }
 
-    fn previous(&self) -> Token {
-        self.tokens[self.current - 1].clone()
+    /// Pulls tokens from the token stream until `count` tokens are buffered. Errors in the
+    /// stream are collected in `self.errors`. Ensures that the buffered tokens end with EOF, even
+    /// if the stream does not.
+    fn fill_to(&mut self, count: usize) {
+        while self.lookahead.len() < count {
+            if self.lookahead.back().is_some_and(|token| token.variant == EOF) {
+                break
+            }
+            match self.tokens.next() {
+                Some(Ok(token)) => {
+                    self.linter.lint(&token);
+                    self.lookahead.push_back(token);
+                },
+                Some(Err(error)) => {
+                    self.errors.push(error);
+                    self.had_error = true;
+                },
+                None => {
+                    let (line, column, end) = match self.lookahead.back().or(self.previous.as_ref()) {
+                        Some(token) => (token.line, token.column + token.lexeme.chars().count(), token.span.end),
+                        None => (1, 1, 0),
+                    };
+                    let span = Span::new(end, end);
+                    self.lookahead.push_back(Token { variant: EOF, lexeme: "", literal: None, line, column, span });
+                },
+            }
+        }
+    }
+
+    /// Consumes the remaining tokens after parsing stopped early, so that errors of the scanner
+    /// after that point are reported and all tokens are linted.
+    fn drain(&mut self) {
+        for result in self.tokens.by_ref() {
+            match result {
+                Ok(token) => self.linter.lint(&token),
+                Err(error) => self.errors.push(error),
+            }
+        }
+    }
+
+    fn peek(&self) -> &Token<'a> {
+        &self.lookahead[0]
+    }
+
+    fn previous(&self) -> &Token<'a> {
+        self.previous.as_ref().expect("previous() called before first token was consumed")
     }
 
     fn at_end(&self) -> bool {
-        self.tokens[self.current].variant == EOF
+        self.peek().variant == EOF
     }
 
     fn next_is_end(&self) -> bool {
-        let at_end = self.at_end();
-        if !at_end {
-            return self.tokens[self.current + 1].variant == EOF
-        }
-        at_end
+        self.at_end() || self.lookahead[1].variant == EOF
+    }
+
+    /// Creates a ParsingError::Report at the current token, which is reported to the user and
+    /// fails the parse.
+    fn error(&mut self, code: Code, message: String) -> ParsingError {
+        let span = self.peek().span;
+        self.error_at(code, message, span, Vec::new())
     }
 
-    /// Creates a new ParsingError variant.
-    /// ParsingError::Report is meant to be reported to the user, while ParsingErrorInternal is
-    /// not.
-    fn error(&mut self, message: String, fatal: bool) -> ParsingError {
-        if fatal { self.had_error = true; }
+    /// Creates a ParsingError::Report detected at the current token, which labels `span` and the
+    /// related source text in `labels`.
+    fn error_at(&mut self, code: Code, message: String, span: Span, labels: Vec<SecondaryLabel>) -> ParsingError {
+        self.had_error = true;
         let token = self.peek();
-        let (line, column) = (token.line, token.column);
+        let error = ParsingError::Report { code, message, line: token.line, column: token.column, span, labels, help: None };
+        self.errors.push(error.clone());
+        error
+    }
 
-        let error = if fatal {
-            ParsingError::Report { message, line, column }
-        } else {
-            ParsingError::Internal { message, line, column}
-        };
+    /// Adds a suggestion how to fix `error`, which was just reported.
+    fn suggest(&mut self, error: ParsingError, help: String) -> ParsingError {
+        let error = error.with_help(help);
+        if let Some(last) = self.errors.last_mut() {
+            *last = error.clone();
+        }
+        error
+    }
+
+    /// Creates a ParsingError::Internal at the current token, which is not reported to the user,
+    /// e.g. when a production fails and the parser tries the next one.
+    fn internal(&mut self, message: String) -> ParsingError {
+        let token = self.peek();
+        let error = ParsingError::Internal { message, line: token.line, column: token.column };
         self.errors.push(error.clone());
         error
     }
 }
+
+/// Labels the opening bracket of an unclosed group or argument list.
+fn unclosed(open: Span) -> SecondaryLabel {
+    SecondaryLabel { message: "Unclosed bracket".to_string(), span: open }
+}
@@ -1,99 +1,194 @@
 use std::fmt;
+use std::ops::Range;
 
+use crate::code::Code;
 use crate::error::ParsingError;
+use crate::span::Span;
 use crate::token_type::TokenType::{self, *};
 
-/// The `Scanner` loops through the source code, identifying tokens and returning them as
-/// Vec<Token>.
+/// The `Scanner` loops through the source code, identifying tokens. It is an iterator over the
+/// scanned tokens and errors, ending with an EOF token. Lexemes borrow from the source.
+/// Whitespace and comments are skipped: line comments start with `#` or `//`, block comments
+/// are enclosed in `/*` and `*/`.
+///
+/// Operators from other languages are scanned as their canonical token types: `and`, `or`,
+/// `not` (in any case), `~=`, `<>`, `=<` and `=>`. With `Strictness::Strict`, they are errors.
 pub struct Scanner<'a> {
     source: &'a str,
     chars: std::str::CharIndices<'a>, // iterator over chars of source
-    tokens: &'a mut Vec<Token>, // stores scanned tokens
     start: Option<(usize, char)>,  // start char of lexeme
     current: Option<(usize, char)>, // current char (byte index, char)
     next: Option<(usize, char)>, // next char  (byte index, char)
     line: usize,  // current line in source
     column: usize, // current column in source
     column_start: usize, // start column of lexeme
-    errors: &'a mut Vec<ParsingError>,
-    pub had_error: bool,
+    previous: Option<TokenType>, // type of previous token
+    done: bool, // EOF token returned
+    strictness: Strictness,
+}
+
+/// Decides whether alternative spellings of operators, ex.: "and" for '&', are accepted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strictness {
+    /// Alternative spellings are scanned as the canonical operators.
+    #[default]
+    Lenient,
+    /// Only canonical operators are accepted.
+    Strict,
+}
+
+impl<'a> Iterator for Scanner<'a> {
+    type Item = Result<Token<'a>, ParsingError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Err(error) = self.skip_whitespace() {
+            return Some(Err(error))
+        }
+        self.start = self.current;
+        self.column_start = self.column;
+
+        if !self.at_end() {
+            let result = self.scan_token();
+            self.previous = result.as_ref().ok().map(|token| token.variant.clone());
+            Some(result)
+        } else if !self.done {
+            self.done = true;
+            Some(self.make_token(EOF, None))
+        } else {
+            None
+        }
+    }
 }
 
 impl<'a> Scanner<'a> {
 
-    pub fn new(source: &'a str, tokens: &'a mut Vec<Token>, errors: &'a mut Vec<ParsingError>) -> Self {
+    pub fn new(source: &'a str) -> Self {
+        Self::with_strictness(source, Strictness::default())
+    }
+
+    pub fn with_strictness(source: &'a str, strictness: Strictness) -> Self {
         let mut chars = source.char_indices();
         let current = chars.next();
         let start = current; 
         let next = chars.clone().next();
 
         Scanner {
-            source: source,
-            chars: chars, 
-            tokens: tokens, //Vec::new(),
-            start: start,
-            current: current,
-            next: next,
+            source,
+            chars, 
+            start,
+            current,
+            next,
             line: 1,
             column: 1,
             column_start: 1,
-            errors: errors,
-            had_error: false,
+            previous: None,
+            done: false,
+            strictness,
         }
     }
 
-    pub fn scan(&mut self) -> Result<(), ParsingError> {
+    /// Skips whitespace and comments. Fails on a block comment without closing `*/`.
+    fn skip_whitespace(&mut self) -> Result<(), ParsingError> {
+        while let Some(c) = self.peek() {
+            match c {
+                ' ' | '\r' | '\t' => { self.advance(); },
+                '\n' => self.newline(),
+                '#' => self.line_comment(),
+                '/' if self.peek_next() == Some('/') => self.line_comment(),
+                '/' if self.peek_next() == Some('*') => self.block_comment()?,
+                _ => break,
+            }
+        }
+        Ok(())
+    }
+
+    fn newline(&mut self) {
+        self.advance();
+        self.line += 1;
+        self.column = 1;
+    }
 
-        while !self.at_end() {
-            self.start = self.current;
-            if let Err(e) = self.scan_token() {
-               self.errors.push(e); 
-               self.had_error = true;
-            }; 
+    /// Skips a comment up to the end of the line, ex.: "# comment" or "// comment".
+    fn line_comment(&mut self) {
+        while matches!(self.peek(), Some(c) if c != '\n') {
+            self.advance();
+        }
+    }
+
+    /// Skips a comment enclosed in `/*` and `*/`, which can span several lines. Block comments
+    /// do not nest.
+    fn block_comment(&mut self) -> Result<(), ParsingError> {
+        let (line, column, start) = (self.line, self.column, self.offset());
+        self.advance(); // consume the '/'
+        self.advance(); // consume the '*'
+        loop {
+            match self.peek() {
+                Some('*') if self.peek_next() == Some('/') => {
+                    self.advance();
+                    self.advance();
+                    return Ok(())
+                },
+                Some('\n') => self.newline(),
+                Some(_) => { self.advance(); },
+                None => {
+                    let span = Span::new(start, self.source.len());
+                    return Err(ParsingError::Report { code: Code::UnterminatedComment, message: "Unterminated block comment".to_string(), line, column, span, labels: Vec::new(), help: None })
+                },
+            }
         }
-        let _ = self.add_token(EOF, None);
-        Ok(()) 
     }
 
-    fn scan_token(&mut self) -> Result<(), ParsingError> {
+    fn scan_token(&mut self) -> Result<Token<'a>, ParsingError> {
 
         let c = match self.advance() {
             Some(ch) => ch,
-            None => return Ok(()),
+            None => return self.make_token(EOF, None),
         };
 
-        //println!("{:?}", c);
-
-        let result = match c {
-            '(' => self.add_token(LeftParen, None),
-            ')' => self.add_token(RightParen, None),
-            '[' => self.add_token(LeftBracket, None),
-            ']' => self.add_token(RightBracket, None),
-            '{' => self.add_token(LeftBrace, None),
-            '}' => self.add_token(RightBrace, None),
-            ',' => self.add_token(Comma, None),
-            // allow '.' only within numbers 20.30?
-            '.' => return Err(ParsingError::Report { message: "'.' only allowed as decimal separator".to_string(), line: self.line, column: self.column - 1}),
-            ':' => self.add_token(Colon, None),
-            ';' => self.add_token(SemiColon, None),
-            '&' => self.add_token(And, None),
-            '|' => self.add_token(Or, None),
-            //'-' => self.add_token(Minus, None), '-' is consumed in number_or_identifier as part
-            //of number. Unary operators currently not supported.
-            '!' => self.match_and_add_token('=', BangEqual, Bang), // Bang really required?
-            '=' => self.match_and_add_token('=', EqualEqual, Equal),
-            '<' => self.match_and_add_token('=', LessEqual, Less),
-            '>' => self.match_and_add_token('=', GreaterEqual, Greater),
-            ' ' | '\r' | '\t'  => Ok(()),  // ignore whitespace
-            '\n' =>  { self.line += 1; self.column = 1; self.column_start = 1;  Ok(()) }, 
-            _ if (c.is_numeric() || c == '-') => self.number_or_identifier(c),
+        match c {
+            '(' => self.make_token(LeftParen, None),
+            ')' => self.make_token(RightParen, None),
+            '[' => self.make_token(LeftBracket, None),
+            ']' => self.make_token(RightBracket, None),
+            '{' => self.make_token(LeftBrace, None),
+            '}' => self.make_token(RightBrace, None),
+            ',' => self.make_token(Comma, None),
+            // allow '.' only within numbers 20.30 and in item ranges hl0010..hl0025
+            '.' if self.match_char('.') => self.make_token(DotDot, None),
+            '.' => {
+                let span = self.lexeme_span()?;
+                let error = self.error(Code::MisplacedDot, "'.' only allowed as decimal separator", span);
+                Err(match self.question_and_item(span) {
+                    Some((question, item)) => error.with_help(format!("Use ';' to separate question and item: '{};{}'", question, item)),
+                    None => error,
+                })
+            },
+            ':' => self.make_token(Colon, None),
+            ';' => self.make_token(SemiColon, None),
+            '&' => self.make_token(And, None),
+            '|' => self.make_token(Or, None),
+            // '-' is a minus operator after an operand, ex.: "hl0001-1", and the sign of a
+            // number or question identifier otherwise, ex.: "-1" or "-1a;hl0001"
+            '-' if self.follows_operand() || !matches!(self.peek(), Some(c) if c.is_numeric()) => self.make_token(Minus, None),
+            '+' => self.make_token(Plus, None),
+            '*' => self.make_token(Star, None),
+            '/' => self.make_token(Slash, None),
+            '!' => self.match_and_make_token('=', BangEqual, Bang),
+            '~' if self.match_char('=') => self.alternative(BangEqual),
+            '=' if self.match_char('<') => self.alternative(LessEqual),
+            '=' if self.match_char('>') => self.alternative(GreaterEqual),
+            '=' => self.match_and_make_token('=', EqualEqual, Equal),
+            '<' if self.match_char('>') => self.alternative(BangEqual),
+            '<' => self.match_and_make_token('=', LessEqual, Less),
+            '>' => self.match_and_make_token('=', GreaterEqual, Greater),
+            '"' | '\'' => self.string(c),
+            _ if (c.is_numeric() || c == '-') => self.number_or_identifier(),
             _ if Self::is_alpha(c) => self.identifier(),
-            _ => return Err(ParsingError::Report { message: "Unexpected character".to_string(), line: self.line, column: self.column - 1}),
-        }; 
-        result
+            _ => Err(self.error(Code::UnexpectedCharacter, "Unexpected character", self.lexeme_span()?)),
+        }
     }
 
-    fn identifier(&mut self) -> Result<(), ParsingError> {
+    fn identifier(&mut self) -> Result<Token<'a>, ParsingError> {
         // Peek and advance as long as current char is alphanumeric
         while let Some(c) = self.peek() {
             if Self::is_alphanumeric(c) {
@@ -103,18 +198,85 @@ impl<'a> Scanner<'a> {
             }
         }
 
-        self.add_token(Identifier, None)
+        // A trailing '*' before ')', ',' or the end makes a pattern, ex.: "any(hl00*)". Otherwise
+        // '*' is a multiplication, ex.: "hl0001*2".
+        if let Some((index, '*')) = self.current
+            && matches!(self.source[index + 1..].trim_start().chars().next(), None | Some(')' | ',')) {
+            self.advance();
+            return self.make_token(Pattern, None)
+        }
+
+        // Logical operators as words, ex.: "and", "OR"
+        let lexeme = &self.source[Range::from(self.lexeme_span()?)];
+        if lexeme.eq_ignore_ascii_case("and") {
+            self.alternative(And)
+        } else if lexeme.eq_ignore_ascii_case("or") {
+            self.alternative(Or)
+        } else if lexeme.eq_ignore_ascii_case("not") {
+            self.alternative(Bang)
+        } else {
+            self.make_token(Identifier, None)
+        }
     }
 
-    fn number_or_identifier(&mut self, c_start: char) -> Result<(), ParsingError> {
-        
-        // Rule out case of minus without number
-        if c_start == '-'  {
-            if !matches!(self.peek(), Some(c) if c.is_numeric()) {
-                return Err(ParsingError::Report { message: "Expected number".to_string(), line: self.line, column: self.column });
+    /// Makes a token for an alternative spelling of an operator, or fails in strict mode.
+    fn alternative(&self, token_type: TokenType) -> Result<Token<'a>, ParsingError> {
+        if self.strictness == Strictness::Strict {
+            let lexeme = &self.source[Range::from(self.lexeme_span()?)];
+            let message = format!("Operator '{}' is not allowed, use '{}'", lexeme, token_type.canonical().unwrap_or_default());
+            return Err(self.error(Code::AlternativeOperator, &message, self.lexeme_span()?))
+        }
+        self.make_token(token_type, None)
+    }
+
+    /// Scans a string literal enclosed in `quote`, ex.: "DE" or 'DE'. Supported escapes are
+    /// `\\`, `\"`, `\'`, `\n` and `\t`. Strings end at the end of the line.
+    fn string(&mut self, quote: char) -> Result<Token<'a>, ParsingError> {
+        let mut value = String::new();
+        let mut error = None;
+        loop {
+            match self.peek() {
+                Some('\n') | None => {
+                    return Err(self.error(Code::UnterminatedString, "Unterminated string", self.lexeme_span()?))
+                },
+                Some(c) if c == quote => {
+                    self.advance();
+                    break
+                },
+                Some('\\') => {
+                    let (column, start) = (self.column, self.offset());
+                    self.advance();
+                    match self.peek() {
+                        Some('n') => value.push('\n'),
+                        Some('t') => value.push('\t'),
+                        Some(c @ ('\\' | '"' | '\'')) => value.push(c),
+                        _ => {
+                            // keep scanning to the closing quote, report the first error only
+                            let end = self.offset() + self.peek().map_or(0, char::len_utf8);
+                            error.get_or_insert(ParsingError::Report {
+                                code: Code::UnknownEscape, message: "Unknown escape sequence".to_string(), line: self.line, column,
+                                span: Span::new(start, end), labels: Vec::new(), help: None,
+                            });
+                            continue
+                        },
+                    }
+                    self.advance();
+                },
+                Some(c) => {
+                    self.advance();
+                    value.push(c);
+                },
             }
         }
 
+        match error {
+            Some(error) => Err(error),
+            None => self.make_token(Str, Some(Literal::Str(value))),
+        }
+    }
+
+    fn number_or_identifier(&mut self) -> Result<Token<'a>, ParsingError> {
+
         // Try to match number
         // Match integer part of decimal
         while matches!(self.peek(), Some(c) if c.is_numeric()) {
@@ -122,12 +284,11 @@ impl<'a> Scanner<'a> {
         }
 
         // Fractional part
-        if let (Some('.'), Some(c_next)) = (self.peek(), self.peek_next()) {
-            if c_next.is_numeric() {
-                self.advance(); // consume the '.'
-                 while matches!(self.peek(), Some(c) if c.is_numeric()) {
-                    self.advance();
-                }
+        if let (Some('.'), Some(c_next)) = (self.peek(), self.peek_next())
+            && c_next.is_numeric() {
+            self.advance(); // consume the '.'
+            while matches!(self.peek(), Some(c) if c.is_numeric()) {
+                self.advance();
             }
         }
 
@@ -140,19 +301,34 @@ impl<'a> Scanner<'a> {
         }
 
         if is_identifier {
-            self.add_token(Identifier, None)
+            self.make_token(Identifier, None)
         } else {
-            self.add_token(Number, None)
+            let literal = self.number_literal()?;
+            self.make_token(Number, Some(literal))
         }
     }
 
-    /// Extracts the string slice source[self.start..self.current]. 
-    fn extract_substring(&self) -> Result<&str, ParsingError> {
+    /// Parses the current lexeme into an integer or, if it has a fractional part, a decimal.
+    fn number_literal(&self) -> Result<Literal, ParsingError> {
+        let span = self.lexeme_span()?;
+        let lexeme = &self.source[span.start..span.end];
+
+        let number = if lexeme.contains('.') {
+            lexeme.parse::<f64>().ok().map(Numeric::Decimal)
+        } else {
+            lexeme.parse::<i64>().ok().map(Numeric::Integer)
+        };
+        number.map(Literal::Number).ok_or(self.error(Code::InvalidNumber, "Invalid number", span))
+    }
+
+    /// Returns the byte span source[self.start..self.current] of the current lexeme.
+    fn lexeme_span(&self) -> Result<Span, ParsingError> {
 
         // Get index of first char of lexeme
         let (start_idx, _) = self.start.ok_or(ParsingError::Report {
-            message: "Indexing into source failed.".to_string(), line: self.line, column: self.column}
-        )?;
+            code: Code::Internal, message: "Indexing into source failed.".to_string(), line: self.line, column: self.column,
+            span: Span::default(), labels: Vec::new(), help: None,
+        })?;
         
         // Get index after last char of lexeme
         let end_idx = if self.at_end() {
@@ -160,12 +336,41 @@ impl<'a> Scanner<'a> {
         } else {
             self.current
                 .map(|(idx, _)| idx)
-                .ok_or(ParsingError::Report {message: "Indexing into source failed.".to_string(), line: self.line, column: self.column})?
+                .ok_or(ParsingError::Report {
+                    code: Code::Internal, message: "Indexing into source failed.".to_string(), line: self.line, column: self.column,
+                    span: Span::default(), labels: Vec::new(), help: None,
+                })?
         };
 
-        Ok(&self.source[start_idx..end_idx])
+        Ok(Span::new(start_idx, end_idx))
      }
 
+    /// Returns the byte index of the current char, or the length of the source at the end.
+    fn offset(&self) -> usize {
+        self.current.map_or(self.source.len(), |(index, _)| index)
+    }
+
+    /// Returns the question and item around a separator at `span`, ex.: "q01" and "hl0001" for
+    /// the '.' of "q01.hl0001". The item must start with a letter.
+    fn question_and_item(&self, span: Span) -> Option<(&'a str, &'a str)> {
+        let before = &self.source[..span.start];
+        let after = &self.source[span.end..];
+        let question = &before[before.trim_end_matches(Self::is_alphanumeric).len()..];
+        let item = &after[..after.len() - after.trim_start_matches(Self::is_alphanumeric).len()];
+        let starts_alpha = item.chars().next().is_some_and(char::is_alphabetic);
+        (!question.is_empty() && starts_alpha).then_some((question, item))
+    }
+
+    /// Creates an error of the current lexeme, which starts at `self.column_start`.
+    fn error(&self, code: Code, message: &str, span: Span) -> ParsingError {
+        ParsingError::Report { code, message: message.to_string(), line: self.line, column: self.column_start, span, labels: Vec::new(), help: None }
+    }
+
+    /// Returns true if the previous token ends an operand of an arithmetic expression.
+    fn follows_operand(&self) -> bool {
+        matches!(self.previous, Some(Identifier | Number | RightParen))
+    }
+
     fn is_alpha(c: char) -> bool {
         c.is_alphabetic() || c == '_'
     }
@@ -174,17 +379,16 @@ impl<'a> Scanner<'a> {
         c.is_alphanumeric() || c == '_' 
     }
 
-    fn match_and_add_token(&mut self, expected: char, type1: TokenType, type2: TokenType) -> Result<(), ParsingError> {
+    fn match_and_make_token(&mut self, expected: char, type1: TokenType, type2: TokenType) -> Result<Token<'a>, ParsingError> {
         let token = if self.match_char(expected) { type1 } else { type2 };
-        self.add_token(token, None)
+        self.make_token(token, None)
     }
 
     fn match_char(&mut self, expected: char) -> bool {
-        if let Some(c) = self.peek() {
-            if c == expected {
-                self.advance_iter();
-                return true
-            }
+        if let Some(c) = self.peek()
+            && c == expected {
+            self.advance_iter();
+            return true
         }
         false
     }
@@ -218,35 +422,42 @@ impl<'a> Scanner<'a> {
         self.next.map(|(_, c)| c)
     }
 
-    fn add_token(&mut self, token_type: TokenType, literal: Option<Literal>) -> Result<(), ParsingError> {
-        let lexeme = if token_type != EOF {
-            self.extract_substring()?.to_string()
+    fn make_token(&self, token_type: TokenType, literal: Option<Literal>) -> Result<Token<'a>, ParsingError> {
+        let span = if token_type != EOF {
+            self.lexeme_span()?
         } else {
-            "".to_string()
+            Span::new(self.source.len(), self.source.len())
         };
-        let token = Token {
+        Ok(Token {
             variant: token_type,
-            lexeme: lexeme, 
-            literal: literal,
+            lexeme: &self.source[span.start..span.end], 
+            literal,
             line: self.line,
             column: self.column_start,
-        };
-        self.tokens.push(token);
-        self.column_start = self.column;
-        Ok(())
+            span,
+        })
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct Token {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Token<'a> {
     pub variant: TokenType,
-    pub lexeme: String,
+    pub lexeme: &'a str,
     pub literal: Option<Literal>, 
     pub line: usize,
     pub column: usize,
+    pub span: Span,
+}
+
+impl Token<'_> {
+    /// Returns true if the lexeme is an alternative spelling of the operator, ex.: "and" or "<>".
+    pub fn is_alternative(&self) -> bool {
+        self.variant.canonical().is_some_and(|canonical| canonical != self.lexeme)
+    }
 }
 
-impl fmt::Display for Token {
+impl fmt::Display for Token<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         write!(f, "{} {}", self.variant, self.lexeme)?;
         if let Some(literal) =  &self.literal {
@@ -257,8 +468,151 @@ impl fmt::Display for Token {
 }
 
 
-#[derive(Debug, Clone, strum_macros::Display)]
+#[derive(Debug, Clone, PartialEq, strum_macros::Display)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Literal {
-    Number(f64),
+    Number(Numeric),
     Str(String),
 }
+
+/// Value of a number literal. Numbers with a fractional part, such as "1.324", are decimals.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(untagged))]
+pub enum Numeric {
+    Integer(i64),
+    Decimal(f64),
+}
+
+impl Numeric {
+    pub fn as_f64(&self) -> f64 {
+        match self {
+            Numeric::Integer(value) => *value as f64,
+            Numeric::Decimal(value) => *value,
+        }
+    }
+
+    /// Returns the negated number, or `None` if an integer overflows, ex.: for `i64::MIN`.
+    pub fn checked_neg(self) -> Option<Numeric> {
+        match self {
+            Numeric::Integer(value) => value.checked_neg().map(Numeric::Integer),
+            Numeric::Decimal(value) => Some(Numeric::Decimal(-value)),
+        }
+    }
+}
+
+impl fmt::Display for Numeric {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Numeric::Integer(value) => write!(f, "{}", value),
+            // keep the decimal point, so that the value is read back as decimal
+            Numeric::Decimal(value) if value.fract() == 0.0 => write!(f, "{:.1}", value),
+            Numeric::Decimal(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scanner_iterator() {
+        let source = "q01;hl0001 = 1.5 @ hl0002";
+        let results: Vec<_> = Scanner::new(source).collect();
+
+        let variants: Vec<_> = results.iter()
+            .map(|result| result.as_ref().map(|token| token.variant.clone()).ok())
+            .collect();
+        assert_eq!(variants, vec![
+            Some(Identifier), Some(SemiColon), Some(Identifier), Some(Equal), Some(Number), None, Some(Identifier), Some(EOF),
+        ]);
+
+        // Lexemes are slices of the source
+        let Ok(token) = &results[2] else { panic!("Expected token") };
+        assert_eq!(token.lexeme, "hl0001");
+        assert!(std::ptr::eq(token.lexeme.as_ptr(), source[4..].as_ptr()));
+        assert_eq!(results[4].as_ref().unwrap().literal, Some(Literal::Number(Numeric::Decimal(1.5))));
+    }
+
+    #[test]
+    fn test_item_sequences() {
+        let variants = |source| Scanner::new(source).map(|result| result.unwrap().variant).collect::<Vec<_>>();
+        assert_eq!(variants("hl0010..hl0025"), vec![Identifier, DotDot, Identifier, EOF]);
+        assert_eq!(variants("(hl00*, hl01* )"), vec![LeftParen, Pattern, Comma, Pattern, RightParen, EOF]);
+        assert_eq!(variants("hl0001*2"), vec![Identifier, Star, Number, EOF]);
+        assert_eq!(variants("hl0001 * (2)"), vec![Identifier, Star, LeftParen, Number, RightParen, EOF]);
+    }
+
+    #[test]
+    fn test_comments() {
+        let source = "# items\nhl0001 = 1 // first\n/* second\n*/ & hl0002/2 = 1 /**/";
+        let tokens: Vec<_> = Scanner::new(source).map(Result::unwrap).collect();
+        let variants: Vec<_> = tokens.iter().map(|token| token.variant.clone()).collect();
+        assert_eq!(variants, vec![Identifier, Equal, Number, And, Identifier, Slash, Number, Equal, Number, EOF]);
+        assert_eq!((tokens[3].line, tokens[3].column), (4, 4));
+
+        let error = Scanner::new("hl0001 = 1 /* * /\n").find_map(Result::err).unwrap();
+        assert_eq!(error.to_string(), "Unterminated block comment (1:12)");
+    }
+
+    #[test]
+    fn test_alternative_operators() {
+        let source = "a~=1 and b<>2 OR Not c=<3 & d=>4 | android=1";
+        let tokens: Vec<_> = Scanner::new(source).map(Result::unwrap).collect();
+        let variants: Vec<_> = tokens.iter().map(|token| token.variant.clone()).collect();
+        assert_eq!(variants, vec![
+            Identifier, BangEqual, Number, And, Identifier, BangEqual, Number, Or, Bang, Identifier, LessEqual, Number,
+            And, Identifier, GreaterEqual, Number, Or, Identifier, Equal, Number, EOF,
+        ]);
+        let alternatives: Vec<_> = tokens.iter().filter(|token| token.is_alternative()).map(|token| token.lexeme).collect();
+        assert_eq!(alternatives, vec!["~=", "and", "<>", "OR", "Not", "=<", "=>"]);
+
+        let errors: Vec<_> = Scanner::with_strictness("a<>1 and b=1", Strictness::Strict).filter_map(Result::err).map(|error| error.to_string()).collect();
+        assert_eq!(errors, vec!["Operator '<>' is not allowed, use '!=' (1:2)", "Operator 'and' is not allowed, use '&' (1:6)"]);
+    }
+
+    #[test]
+    fn test_strings() {
+        let cases = vec![
+            (r#""DE""#, "DE"),
+            ("'DE'", "DE"),
+            (r#"'it\'s' "#, "it's"),
+            (r#""say \"hi\"""#, r#"say "hi""#),
+            (r#"'a\\b\tc\n'"#, "a\\b\tc\n"),
+            (r#""'""#, "'"),
+        ];
+        for (source, expected) in cases {
+            let token = Scanner::new(source).next().unwrap().unwrap();
+            assert_eq!(token.variant, Str, "Input: {}", source);
+            assert_eq!(token.literal, Some(Literal::Str(expected.to_string())), "Input: {}", source);
+        }
+
+        let errors = vec![
+            ("'DE", "Unterminated string (1:1)"),
+            ("x = 'DE\n'", "Unterminated string (1:5)"),
+            (r#""D\qE""#, "Unknown escape sequence (1:3)"),
+        ];
+        for (source, expected) in errors {
+            let error = Scanner::new(source).find_map(Result::err).unwrap();
+            assert_eq!(error.to_string(), expected, "Input: {}", source);
+        }
+    }
+
+    #[test]
+    fn test_parse_token_stream() {
+        use crate::parser::Parser;
+
+        // The parser appends EOF if the token stream does not end with it
+        let tokens = Scanner::new("q01;hl0001=1 & hl0002=2").filter(|result| !matches!(result, Ok(Token { variant: EOF, .. })));
+        let mut parser = Parser::new(tokens);
+        assert!(parser.parse().is_ok());
+
+        let mut parser = Parser::new(Scanner::new("q01;hl0001=1 & hl0002=@"));
+        assert!(parser.parse().is_err());
+        assert!(parser.had_error);
+        assert!(parser.errors().iter().any(|error| error.to_string() == "Unexpected character (1:23)"));
+    }
+}
@@ -1,16 +1,39 @@
 #[derive(Debug, Clone, PartialEq, strum_macros::Display)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TokenType {
     LeftParen, RightParen, // ()
     LeftBracket, RightBracket, // []
     LeftBrace, RightBrace, // {}
-    Comma, Colon, SemiColon,
+    Comma, Colon, SemiColon, DotDot,
     Bang, BangEqual,
     Equal, EqualEqual,
     Greater, GreaterEqual,
     Less, LessEqual,
     And, Or,
-    Minus,
+    Minus, Plus, Star, Slash,
     Number,
+    Str,
     Identifier,
+    Pattern, // item pattern, ex.: hl00*
     EOF,
 }
+
+impl TokenType {
+    /// Returns the canonical spelling of an operator, ex.: "&" for `And`, which is also scanned
+    /// from "and".
+    pub fn canonical(&self) -> Option<&'static str> {
+        match self {
+            TokenType::Bang => Some("!"),
+            TokenType::BangEqual => Some("!="),
+            TokenType::Equal => Some("="),
+            TokenType::EqualEqual => Some("=="),
+            TokenType::Greater => Some(">"),
+            TokenType::GreaterEqual => Some(">="),
+            TokenType::Less => Some("<"),
+            TokenType::LessEqual => Some("<="),
+            TokenType::And => Some("&"),
+            TokenType::Or => Some("|"),
+            _ => None,
+        }
+    }
+}